@@ -1,21 +1,104 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Device, Stream, StreamConfig};
+use cpal::{Device, FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
 use rubato::{FftFixedIn, Resampler};
+use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::ring;
 
 const TARGET_SAMPLE_RATE: u32 = 16_000;
+/// How often the collector thread moves samples out of the realtime queue
+const COLLECT_INTERVAL: Duration = Duration::from_millis(10);
+/// Realtime queue size; the collector only has to keep up within this window
+const QUEUE_SECS: usize = 2;
 
 pub struct AudioRecorder {
     device: Device,
     config: StreamConfig,
-    buffer: Arc<Mutex<Vec<f32>>>,
+    sample_format: SampleFormat,
+    buffer: Arc<Mutex<Capture>>,
     stream: Option<Stream>,
+    /// Stream stays open between recordings to feed the pre-roll ring
+    always_open: bool,
+    /// Resamples audio handed out by `drain` while a recording is running
+    drain_resampler: Option<StreamResampler>,
     display_name: String,
 }
 
+/// Captured mono audio. The realtime callback only pushes into a lock-free
+/// queue; a collector thread moves queued samples in here. While idle,
+/// samples go to a fixed-size pre-roll ring; while recording, to `samples`.
+struct Capture {
+    queue: Option<ring::Consumer>,
+    recording: bool,
+    samples: Vec<f32>,
+    preroll: VecDeque<f32>,
+    preroll_len: usize,
+}
+
+impl Capture {
+    fn new(preroll_len: usize) -> Self {
+        Self {
+            queue: None,
+            recording: false,
+            samples: Vec::new(),
+            preroll: VecDeque::with_capacity(preroll_len),
+            preroll_len,
+        }
+    }
+
+    fn push(&mut self, sample: f32) {
+        if self.recording {
+            self.samples.push(sample);
+        } else if self.preroll_len > 0 {
+            if self.preroll.len() == self.preroll_len {
+                self.preroll.pop_front();
+            }
+            self.preroll.push_back(sample);
+        }
+    }
+
+    /// Move everything the realtime callback has queued into the capture.
+    fn collect(&mut self) {
+        let Some(mut queue) = self.queue.take() else {
+            return;
+        };
+        queue.pop_each(|sample| self.push(sample));
+        let dropped = queue.take_dropped();
+        if dropped > 0 {
+            eprintln!("warning: audio capture overrun, dropped {dropped} samples");
+        }
+        self.queue = Some(queue);
+    }
+
+    /// Start a recording, seeded with the pre-roll captured so far.
+    fn begin(&mut self) {
+        self.collect();
+        self.samples.clear();
+        self.samples.extend(self.preroll.drain(..));
+        self.recording = true;
+    }
+
+    /// Take the samples recorded so far without ending the recording.
+    fn take(&mut self) -> Vec<f32> {
+        self.collect();
+        std::mem::take(&mut self.samples)
+    }
+
+    fn finish(&mut self) -> Vec<f32> {
+        self.collect();
+        self.recording = false;
+        std::mem::take(&mut self.samples)
+    }
+}
+
 impl AudioRecorder {
-    pub fn new(device_name: Option<&str>) -> Result<Self> {
+    /// With a non-zero `preroll_ms` the input stream is kept open while idle
+    /// and the last `preroll_ms` of audio is prepended to each recording.
+    pub fn new(device_name: Option<&str>, preroll_ms: u64) -> Result<Self> {
         let host = cpal::default_host();
 
         let (device, display_name) = match device_name {
@@ -65,61 +148,131 @@ impl AudioRecorder {
         };
 
         let supported = device.default_input_config()?;
+        let sample_format = supported.sample_format();
         let config: StreamConfig = supported.into();
+        let preroll_len = config.sample_rate.0 as usize * preroll_ms as usize / 1000;
 
-        Ok(Self {
+        let mut recorder = Self {
             device,
             config,
-            buffer: Arc::new(Mutex::new(Vec::new())),
+            sample_format,
+            buffer: Arc::new(Mutex::new(Capture::new(preroll_len))),
             stream: None,
+            always_open: preroll_len > 0,
+            drain_resampler: None,
             display_name,
-        })
+        };
+        if recorder.always_open {
+            recorder.stream = Some(recorder.build_stream()?);
+        }
+
+        // Collector thread: exits once the recorder is dropped
+        let capture = Arc::downgrade(&recorder.buffer);
+        thread::spawn(move || {
+            while let Some(capture) = capture.upgrade() {
+                capture.lock().expect("audio buffer poisoned").collect();
+                drop(capture);
+                thread::sleep(COLLECT_INTERVAL);
+            }
+        });
+        Ok(recorder)
     }
 
-    pub fn start(&mut self) -> Result<()> {
-        // Clear previous recording and pre-allocate for ~60s to avoid
-        // reallocations in the realtime audio callback
-        let mut buf = self.buffer.lock().expect("audio buffer poisoned");
-        buf.clear();
-        buf.reserve(self.config.sample_rate.0 as usize * 60);
-        drop(buf);
-
-        let buffer = Arc::clone(&self.buffer);
+    fn build_stream(&self) -> Result<Stream> {
+        let stream = match self.sample_format {
+            SampleFormat::F32 => self.build_stream_as::<f32>()?,
+            SampleFormat::F64 => self.build_stream_as::<f64>()?,
+            SampleFormat::I8 => self.build_stream_as::<i8>()?,
+            SampleFormat::I16 => self.build_stream_as::<i16>()?,
+            SampleFormat::I32 => self.build_stream_as::<i32>()?,
+            SampleFormat::I64 => self.build_stream_as::<i64>()?,
+            SampleFormat::U8 => self.build_stream_as::<u8>()?,
+            SampleFormat::U16 => self.build_stream_as::<u16>()?,
+            SampleFormat::U32 => self.build_stream_as::<u32>()?,
+            SampleFormat::U64 => self.build_stream_as::<u64>()?,
+            other => bail!("unsupported input sample format: {other}"),
+        };
+        stream.play()?;
+        Ok(stream)
+    }
+
+    fn build_stream_as<T>(&self) -> Result<Stream>
+    where
+        T: SizedSample,
+        f32: FromSample<T>,
+    {
         let channels = self.config.channels as usize;
+        let (mut producer, consumer) =
+            ring::channel(self.config.sample_rate.0 as usize * QUEUE_SECS);
 
         let err_fn = |err| eprintln!("audio stream error: {err}");
 
+        // The callback never locks or allocates: it only fills the queue
         let stream = self.device.build_input_stream(
             &self.config,
-            move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                // Downmix to mono inline
-                let mut buf = buffer.lock().expect("audio buffer poisoned");
-                for chunk in data.chunks(channels) {
-                    let mono: f32 = chunk.iter().sum::<f32>() / channels as f32;
-                    buf.push(mono);
-                }
+            move |data: &[T], _: &cpal::InputCallbackInfo| {
+                downmix(data, channels, |mono| {
+                    producer.push(mono);
+                });
             },
             err_fn,
             None,
         )?;
+        self.buffer.lock().expect("audio buffer poisoned").queue = Some(consumer);
+        Ok(stream)
+    }
 
-        stream.play()?;
-        self.stream = Some(stream);
+    pub fn start(&mut self) -> Result<()> {
+        self.buffer.lock().expect("audio buffer poisoned").begin();
+
+        let source_rate = self.config.sample_rate.0 as usize;
+        self.drain_resampler = if source_rate == TARGET_SAMPLE_RATE as usize {
+            None
+        } else {
+            Some(StreamResampler::new(
+                source_rate,
+                TARGET_SAMPLE_RATE as usize,
+            )?)
+        };
+
+        if !self.always_open {
+            self.stream = Some(self.build_stream()?);
+        }
         Ok(())
     }
 
+    /// Return the 16 kHz audio captured since `start` or the previous call,
+    /// while recording continues. `stop` then returns only the remainder.
+    pub fn drain(&mut self) -> Result<Vec<f32>> {
+        let raw = self.buffer.lock().expect("audio buffer poisoned").take();
+        match self.drain_resampler.as_mut() {
+            Some(resampler) => resampler.process(&raw),
+            None => Ok(raw),
+        }
+    }
+
     pub fn stop(&mut self) -> Result<Vec<f32>> {
-        // Drop stream to stop recording
-        self.stream.take();
+        // Drop stream to stop recording, unless it feeds the pre-roll
+        if !self.always_open {
+            self.stream.take();
+        }
 
-        let raw = std::mem::take(&mut *self.buffer.lock().expect("audio buffer poisoned"));
-        let source_rate = self.config.sample_rate.0 as usize;
+        let raw = self.buffer.lock().expect("audio buffer poisoned").finish();
 
-        if source_rate == TARGET_SAMPLE_RATE as usize {
-            return Ok(raw);
+        // Continue with the resampler used by `drain`, so drained audio and
+        // the rest line up without a seam
+        match self.drain_resampler.take() {
+            Some(mut resampler) => resampler.finish(&raw),
+            None => Ok(raw),
         }
+    }
 
-        resample(&raw, source_rate, TARGET_SAMPLE_RATE as usize)
+    /// Stop recording and discard the captured audio.
+    pub fn cancel(&mut self) {
+        if !self.always_open {
+            self.stream.take();
+        }
+        self.buffer.lock().expect("audio buffer poisoned").finish();
     }
 
     pub fn sample_rate(&self) -> u32 {
@@ -201,44 +354,226 @@ fn default_source_description() -> Option<String> {
         .map(|(_, desc, _)| desc)
 }
 
-fn resample(input: &[f32], from_rate: usize, to_rate: usize) -> Result<Vec<f32>> {
-    if input.is_empty() {
-        return Ok(Vec::new());
+/// Convert interleaved samples of any cpal format to f32 and downmix to mono.
+fn downmix<T>(data: &[T], channels: usize, mut push: impl FnMut(f32))
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    for chunk in data.chunks(channels) {
+        let sum: f32 = chunk.iter().map(|s| s.to_sample::<f32>()).sum();
+        push(sum / channels as f32);
     }
+}
 
-    let chunk_size = 1024;
-    let mut resampler = FftFixedIn::<f32>::new(from_rate, to_rate, chunk_size, 2, 1)?;
+/// Resamples a stream delivered in arbitrary pieces, carrying incomplete
+/// chunks over to the next call so there are no seams between pieces.
+struct StreamResampler {
+    resampler: FftFixedIn<f32>,
+    pending: Vec<f32>,
+    from_rate: usize,
+    to_rate: usize,
+}
 
-    let mut output = Vec::with_capacity(input.len() * to_rate / from_rate + 1024);
+impl StreamResampler {
+    fn new(from_rate: usize, to_rate: usize) -> Result<Self> {
+        Ok(Self {
+            resampler: FftFixedIn::<f32>::new(from_rate, to_rate, 1024, 2, 1)?,
+            pending: Vec::new(),
+            from_rate,
+            to_rate,
+        })
+    }
 
-    // Process full chunks
-    let mut pos = 0;
-    while pos + chunk_size <= input.len() {
-        let chunk = &input[pos..pos + chunk_size];
-        let result = resampler.process(&[chunk], None)?;
-        output.extend_from_slice(&result[0]);
-        pos += chunk_size;
+    fn process(&mut self, input: &[f32]) -> Result<Vec<f32>> {
+        self.pending.extend_from_slice(input);
+        let chunk_size = self.resampler.input_frames_next();
+        let mut output = Vec::with_capacity(self.pending.len() * self.to_rate / self.from_rate);
+        let mut pos = 0;
+        while pos + chunk_size <= self.pending.len() {
+            let result = self
+                .resampler
+                .process(&[&self.pending[pos..pos + chunk_size]], None)?;
+            output.extend_from_slice(&result[0]);
+            pos += chunk_size;
+        }
+        self.pending.drain(..pos);
+        Ok(output)
     }
 
-    // Process remaining samples by padding with zeros
-    if pos < input.len() {
-        let mut last_chunk = vec![0.0f32; chunk_size];
-        let remaining = input.len() - pos;
-        last_chunk[..remaining].copy_from_slice(&input[pos..]);
-        let result = resampler.process(&[&last_chunk], None)?;
+    /// Process the last piece of the stream, zero-padding the final chunk.
+    fn finish(&mut self, input: &[f32]) -> Result<Vec<f32>> {
+        let mut output = self.process(input)?;
+        if self.pending.is_empty() {
+            return Ok(output);
+        }
+        let remaining = self.pending.len();
+        let mut last_chunk = vec![0.0f32; self.resampler.input_frames_next()];
+        last_chunk[..remaining].copy_from_slice(&self.pending);
+        self.pending.clear();
+        let result = self.resampler.process(&[&last_chunk], None)?;
         // Only take proportional output
-        let expected = remaining * to_rate / from_rate;
+        let expected = remaining * self.to_rate / self.from_rate;
         let take = expected.min(result[0].len());
         output.extend_from_slice(&result[0][..take]);
+        Ok(output)
     }
-
-    Ok(output)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// One-shot resampling of a whole recording, as `stop` does without drains
+    fn resample(input: &[f32], from_rate: usize, to_rate: usize) -> Result<Vec<f32>> {
+        StreamResampler::new(from_rate, to_rate)?.finish(input)
+    }
+
+    fn downmix_all<T>(data: &[T], channels: usize) -> Vec<f32>
+    where
+        T: SizedSample,
+        f32: FromSample<T>,
+    {
+        let mut out = Vec::new();
+        downmix(data, channels, |s| out.push(s));
+        out
+    }
+
+    #[test]
+    fn downmix_f32_stereo() {
+        assert_eq!(downmix_all(&[0.5f32, -0.5, 1.0, 0.0], 2), vec![0.0, 0.5]);
+    }
+
+    #[test]
+    fn downmix_i16_to_f32() {
+        let out = downmix_all(&[i16::MIN, 0, i16::MAX], 1);
+        assert_eq!(out[0], -1.0);
+        assert_eq!(out[1], 0.0);
+        assert!((out[2] - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn downmix_u16_to_f32() {
+        // Unsigned formats are centered on the midpoint
+        let out = downmix_all(&[0u16, 32768, u16::MAX], 1);
+        assert_eq!(out[0], -1.0);
+        assert_eq!(out[1], 0.0);
+        assert!((out[2] - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn downmix_i16_stereo() {
+        let out = downmix_all(&[i16::MAX, i16::MIN, 16384i16, 16384], 2);
+        assert!(out[0].abs() < 1e-4);
+        assert!((out[1] - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn downmix_other_integer_formats() {
+        assert_eq!(downmix_all(&[128u8], 1), vec![0.0]);
+        assert_eq!(downmix_all(&[i32::MIN], 1), vec![-1.0]);
+        assert_eq!(downmix_all(&[0.25f64], 1), vec![0.25]);
+    }
+
+    #[test]
+    fn capture_without_preroll_drops_idle_samples() {
+        let mut c = Capture::new(0);
+        c.push(0.1);
+        c.begin();
+        c.push(0.2);
+        assert_eq!(c.finish(), vec![0.2]);
+    }
+
+    #[test]
+    fn capture_preroll_keeps_last_samples() {
+        let mut c = Capture::new(3);
+        for s in [0.1, 0.2, 0.3, 0.4, 0.5] {
+            c.push(s);
+        }
+        c.begin();
+        c.push(0.6);
+        assert_eq!(c.finish(), vec![0.3, 0.4, 0.5, 0.6]);
+    }
+
+    #[test]
+    fn capture_preroll_refills_after_recording() {
+        let mut c = Capture::new(2);
+        c.push(0.1);
+        c.begin();
+        c.push(0.2);
+        assert_eq!(c.finish(), vec![0.1, 0.2]);
+        // Samples recorded earlier must not leak into the next pre-roll
+        c.push(0.3);
+        c.begin();
+        assert_eq!(c.finish(), vec![0.3]);
+    }
+
+    #[test]
+    fn capture_collects_from_queue() {
+        let (mut producer, consumer) = ring::channel(8);
+        let mut c = Capture::new(2);
+        c.queue = Some(consumer);
+        for s in [0.1, 0.2, 0.3] {
+            producer.push(s);
+        }
+        // Idle samples land in the pre-roll, then recording picks up new ones
+        c.begin();
+        producer.push(0.4);
+        assert_eq!(c.finish(), vec![0.2, 0.3, 0.4]);
+    }
+
+    #[test]
+    fn capture_has_no_length_limit() {
+        let (mut producer, consumer) = ring::channel(1024);
+        let mut c = Capture::new(0);
+        c.queue = Some(consumer);
+        c.begin();
+        // Well past the old 60s reservation at 48kHz, collected in batches
+        let total = 48_000 * 90;
+        for _ in 0..total / 1000 {
+            for _ in 0..1000 {
+                producer.push(0.0);
+            }
+            c.collect();
+        }
+        assert_eq!(c.finish().len(), total);
+    }
+
+    #[test]
+    fn capture_take_keeps_recording() {
+        let mut c = Capture::new(0);
+        c.begin();
+        c.push(0.1);
+        assert_eq!(c.take(), vec![0.1]);
+        c.push(0.2);
+        assert_eq!(c.finish(), vec![0.2]);
+    }
+
+    #[test]
+    fn stream_resampler_matches_rate_across_pieces() {
+        let mut r = StreamResampler::new(48000, 16000).unwrap();
+        // 1s at 48kHz delivered in 100 callback-sized pieces, none chunk-aligned
+        let total: usize = (0..100)
+            .map(|_| r.process(&[0.1f32; 480]).unwrap().len())
+            .sum();
+        let ratio = total as f64 / 16000.0;
+        assert!((0.9..=1.05).contains(&ratio), "got {total} samples");
+    }
+
+    #[test]
+    fn stream_resampler_drained_pieces_match_one_shot() {
+        let input: Vec<f32> = (0..30_000).map(|i| (i as f32 * 0.01).sin()).collect();
+        let whole = resample(&input, 44100, 16000).unwrap();
+
+        let mut r = StreamResampler::new(44100, 16000).unwrap();
+        let mut pieces = Vec::new();
+        for piece in input[..25_000].chunks(700) {
+            pieces.extend(r.process(piece).unwrap());
+        }
+        pieces.extend(r.finish(&input[25_000..]).unwrap());
+        assert_eq!(pieces, whole);
+    }
+
     #[test]
     fn resample_empty_input() {
         let result = resample(&[], 44100, 16000).unwrap();
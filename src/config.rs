@@ -7,14 +7,39 @@ use std::path::PathBuf;
 pub struct Config {
     #[serde(default = "default_hotkey")]
     pub hotkey: String,
-    #[serde(default = "default_backend")]
-    pub backend: String,
+    #[serde(default = "default_hotkey_backend")]
+    pub hotkey_backend: HotkeyBackend,
+    #[serde(default)]
+    pub evdev_device: Option<String>,
+    #[serde(default = "default_cancel_key")]
+    pub cancel_key: String,
+    #[serde(default = "default_activation")]
+    pub activation: Activation,
+    #[serde(default = "default_hold_threshold")]
+    pub hold_threshold_ms: u64,
+    /// Backends to try in order; a single name is accepted as a one-entry list
+    #[serde(default = "default_backend", deserialize_with = "one_or_many")]
+    pub backend: Vec<String>,
     #[serde(default = "default_model_path")]
     pub model_path: String,
+    /// Local models picked by clip length; `model_path` is used when none
+    /// of the rules fits and none of them is a catch-all
+    #[serde(default)]
+    pub models: Vec<ModelRule>,
     #[serde(default)]
     pub api_key: String,
     #[serde(default = "default_api_url")]
     pub api_url: String,
+    /// Program and arguments for the command backend. `{language}`,
+    /// `{prompt}`, `{task}` and `{file}` are substituted in each argument
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default = "default_command_timeout")]
+    pub command_timeout_ms: u64,
+    /// Accept output from a command that also wrote to stderr; by default
+    /// any stderr output is treated as a failure
+    #[serde(default)]
+    pub command_ignore_stderr: bool,
     #[serde(default = "default_output_mode")]
     pub output_mode: OutputMode,
     #[serde(default = "default_typing_delay")]
@@ -24,13 +49,50 @@ pub struct Config {
     #[serde(default)]
     pub device: Option<String>,
     #[serde(default)]
+    pub preroll_ms: u64,
+    #[serde(default = "default_true")]
+    pub warm_up: bool,
+    #[serde(default)]
     pub language: Option<String>,
+    /// Auto-detect among these languages when `language` is unset
+    #[serde(default)]
+    pub languages: Vec<String>,
+    #[serde(default = "default_task")]
+    pub task: Task,
     #[serde(default)]
     pub initial_prompt: Option<String>,
     #[serde(default)]
+    pub streaming: bool,
+    #[serde(default = "default_streaming_step")]
+    pub streaming_step_ms: u64,
+    #[serde(default)]
+    pub whisper: WhisperConfig,
+    #[serde(default)]
+    pub api: ApiConfig,
+    /// Top-level spelling of `[api] audio_format`, which it overrides
+    #[serde(default)]
+    pub api_audio_format: Option<AudioFormat>,
+    #[serde(default)]
+    pub whisper_server: WhisperServerConfig,
+    #[serde(default)]
+    pub wyoming: WyomingConfig,
+    #[serde(default)]
+    pub filter: FilterConfig,
+    #[serde(default)]
+    pub vad: VadConfig,
+    #[serde(default)]
     pub replacements: Replacements,
 }
 
+/// One `[[models]]` entry: use `path` for clips up to `max_secs` long.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct ModelRule {
+    /// No limit when unset, which makes the rule a catch-all
+    #[serde(default)]
+    pub max_secs: Option<f32>,
+    pub path: String,
+}
+
 #[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum OutputMode {
@@ -38,6 +100,38 @@ pub enum OutputMode {
     Type,
 }
 
+/// What the backend produces from the speech.
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Task {
+    /// Text in the spoken language
+    Transcribe,
+    /// English text, whatever the spoken language
+    Translate,
+}
+
+/// Where key events are read from.
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum HotkeyBackend {
+    /// X11 global key hook
+    Rdev,
+    /// Raw /dev/input devices (Wayland, headless)
+    Evdev,
+}
+
+/// How the hotkey drives recording.
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Activation {
+    /// Record while the hotkey is held (push-to-talk)
+    Hold,
+    /// One tap starts recording, the next tap stops it
+    Toggle,
+    /// Short tap latches like toggle, long hold acts as push-to-talk
+    Hybrid,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Replacements {
     #[serde(default = "default_true")]
@@ -50,6 +144,388 @@ fn default_true() -> bool {
     true
 }
 
+/// Accept either `key = "a"` or `key = ["a", "b"]`.
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+/// Decoding parameters for the local whisper backend. Defaults match
+/// whisper.cpp's own, except `best_of`: 1 instead of 5, as dictr has always
+/// decoded, which keeps temperature fallbacks fast.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WhisperConfig {
+    #[serde(default = "default_strategy")]
+    pub strategy: Strategy,
+    /// Candidates sampled per temperature with greedy decoding
+    #[serde(default = "default_best_of")]
+    pub best_of: u32,
+    /// Beams kept with beam search
+    #[serde(default = "default_beam_size")]
+    pub beam_size: u32,
+    #[serde(default)]
+    pub temperature: f32,
+    /// Added to the temperature when a decode fails the thresholds below
+    #[serde(default = "default_temperature_inc")]
+    pub temperature_inc: f32,
+    /// Inference threads; whisper.cpp picks min(4, cores) when unset
+    #[serde(default)]
+    pub n_threads: Option<u32>,
+    /// Do not condition on previously decoded text
+    #[serde(default = "default_true")]
+    pub no_context: bool,
+    #[serde(default = "default_true")]
+    pub suppress_blank: bool,
+    #[serde(default)]
+    pub suppress_non_speech_tokens: bool,
+    /// Force a single output segment
+    #[serde(default)]
+    pub single_segment: bool,
+    /// Retry at a higher temperature above this compression entropy
+    #[serde(default = "default_entropy_thold")]
+    pub entropy_thold: f32,
+    /// Retry at a higher temperature below this average log probability
+    #[serde(default = "default_logprob_thold")]
+    pub logprob_thold: f32,
+    /// whisper.cpp skips a window as silence when its no-speech probability
+    /// is above this and the average log probability is below `logprob_thold`
+    #[serde(default = "default_no_speech_thold")]
+    pub no_speech_thold: f32,
+    /// Compute per-word timing (used by `--format json` and `vtt`)
+    #[serde(default)]
+    pub word_timestamps: bool,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy {
+    Greedy,
+    Beam,
+}
+
+fn default_strategy() -> Strategy {
+    Strategy::Greedy
+}
+fn default_best_of() -> u32 {
+    1
+}
+fn default_beam_size() -> u32 {
+    5
+}
+fn default_temperature_inc() -> f32 {
+    0.2
+}
+fn default_entropy_thold() -> f32 {
+    2.4
+}
+fn default_logprob_thold() -> f32 {
+    -1.0
+}
+fn default_no_speech_thold() -> f32 {
+    0.6
+}
+
+impl Default for WhisperConfig {
+    fn default() -> Self {
+        Self {
+            strategy: default_strategy(),
+            best_of: default_best_of(),
+            beam_size: default_beam_size(),
+            temperature: 0.0,
+            temperature_inc: default_temperature_inc(),
+            n_threads: None,
+            no_context: true,
+            suppress_blank: true,
+            suppress_non_speech_tokens: false,
+            single_segment: false,
+            entropy_thold: default_entropy_thold(),
+            logprob_thold: default_logprob_thold(),
+            no_speech_thold: default_no_speech_thold(),
+            word_timestamps: false,
+        }
+    }
+}
+
+/// Request options for the api backend, so OpenAI-compatible servers
+/// (faster-whisper-server, LocalAI, Groq, ...) can be used.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiConfig {
+    #[serde(default = "default_api_model")]
+    pub model: String,
+    #[serde(default = "default_response_format")]
+    pub response_format: ResponseFormat,
+    /// Encoding of the uploaded audio
+    #[serde(default = "default_audio_format")]
+    pub audio_format: AudioFormat,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// Extra HTTP headers sent with every request
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Extra multipart form fields sent with every request
+    #[serde(default)]
+    pub fields: HashMap<String, String>,
+    #[serde(default = "default_api_connect_timeout")]
+    pub connect_timeout_ms: u64,
+    /// Limit for a whole request, upload and response included
+    #[serde(default = "default_api_timeout")]
+    pub timeout_ms: u64,
+    /// Retries after a timeout, connection error, 408, 429 or 5xx response
+    #[serde(default = "default_api_retries")]
+    pub retries: u32,
+    /// Delay before the first retry, doubled on each further one (plus
+    /// jitter) unless the server sends `Retry-After`. A `Retry-After` over
+    /// 10 s fails the request instead of waiting
+    #[serde(default = "default_api_retry_backoff")]
+    pub retry_backoff_ms: u64,
+}
+
+/// What a server backend is asked to return.
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFormat {
+    /// `{"text": ...}`
+    Json,
+    /// Text plus timestamped segments and the detected language
+    VerboseJson,
+    /// The bare transcription
+    Text,
+}
+
+impl ResponseFormat {
+    /// The `response_format` form value
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ResponseFormat::Json => "json",
+            ResponseFormat::VerboseJson => "verbose_json",
+            ResponseFormat::Text => "text",
+        }
+    }
+}
+
+/// How recordings are encoded for upload.
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    /// 16-bit PCM, about 1.9 MB per minute
+    Wav,
+    /// Lossless, typically around half the size of WAV for speech
+    Flac,
+    /// Lossy Opus in an Ogg container, about 180 KB per minute. Needs the
+    /// `opus` cargo feature
+    #[serde(rename = "ogg-opus")]
+    OggOpus,
+}
+
+fn default_api_model() -> String {
+    "whisper-1".into()
+}
+fn default_response_format() -> ResponseFormat {
+    ResponseFormat::Json
+}
+fn default_audio_format() -> AudioFormat {
+    AudioFormat::Wav
+}
+fn default_api_connect_timeout() -> u64 {
+    5000
+}
+fn default_api_timeout() -> u64 {
+    30_000
+}
+fn default_api_retries() -> u32 {
+    2
+}
+fn default_api_retry_backoff() -> u64 {
+    500
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            model: default_api_model(),
+            response_format: default_response_format(),
+            audio_format: default_audio_format(),
+            temperature: None,
+            headers: HashMap::new(),
+            fields: HashMap::new(),
+            connect_timeout_ms: default_api_connect_timeout(),
+            timeout_ms: default_api_timeout(),
+            retries: default_api_retries(),
+            retry_backoff_ms: default_api_retry_backoff(),
+        }
+    }
+}
+
+/// The whisper-server backend: a whisper.cpp server's `/inference` endpoint.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WhisperServerConfig {
+    #[serde(default = "default_whisper_server_url")]
+    pub url: String,
+    #[serde(default = "default_whisper_server_response_format")]
+    pub response_format: ResponseFormat,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default = "default_api_timeout")]
+    pub timeout_ms: u64,
+    /// Retries after a timeout, connection error, 408, 429 or 5xx response
+    #[serde(default = "default_api_retries")]
+    pub retries: u32,
+}
+
+fn default_whisper_server_url() -> String {
+    "http://127.0.0.1:8080/inference".into()
+}
+fn default_whisper_server_response_format() -> ResponseFormat {
+    ResponseFormat::VerboseJson
+}
+
+impl Default for WhisperServerConfig {
+    fn default() -> Self {
+        Self {
+            url: default_whisper_server_url(),
+            response_format: default_whisper_server_response_format(),
+            temperature: None,
+            timeout_ms: default_api_timeout(),
+            retries: default_api_retries(),
+        }
+    }
+}
+
+/// The wyoming backend: an ASR service speaking the Wyoming protocol
+/// (wyoming-faster-whisper, wyoming-vosk, ...).
+#[derive(Debug, Clone, Deserialize)]
+pub struct WyomingConfig {
+    /// `host:port` of the service
+    #[serde(default = "default_wyoming_address")]
+    pub address: String,
+    /// Model to ask for, when the service offers several
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default = "default_api_timeout")]
+    pub timeout_ms: u64,
+}
+
+fn default_wyoming_address() -> String {
+    "127.0.0.1:10300".into()
+}
+
+impl Default for WyomingConfig {
+    fn default() -> Self {
+        Self {
+            address: default_wyoming_address(),
+            model: None,
+            timeout_ms: default_api_timeout(),
+        }
+    }
+}
+
+/// Drops transcribed segments that are likely hallucinations.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FilterConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Segments whose mean token log probability is below this are dropped
+    /// (only for backends that report it)
+    #[serde(default = "default_min_avg_logprob")]
+    pub min_avg_logprob: f32,
+    /// Segments whose no-speech probability is above this are dropped (only
+    /// for backends that report it)
+    #[serde(default = "default_max_no_speech_prob")]
+    pub max_no_speech_prob: f32,
+    /// Segments consisting only of one of these phrases are dropped. Matching
+    /// ignores case and surrounding punctuation.
+    #[serde(default = "default_hallucination_phrases")]
+    pub phrases: Vec<String>,
+}
+
+fn default_min_avg_logprob() -> f32 {
+    -1.0
+}
+fn default_max_no_speech_prob() -> f32 {
+    0.8
+}
+fn default_hallucination_phrases() -> Vec<String> {
+    [
+        "thank you for watching",
+        "thanks for watching",
+        "thank you for watching and see you next time",
+        "please subscribe",
+        "like and subscribe",
+        "subtitles by the amara.org community",
+        "transcribed by https://otter.ai",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_avg_logprob: default_min_avg_logprob(),
+            max_no_speech_prob: default_max_no_speech_prob(),
+            phrases: default_hallucination_phrases(),
+        }
+    }
+}
+
+/// Energy-based voice activity detection, applied before transcription.
+#[derive(Debug, Deserialize)]
+pub struct VadConfig {
+    /// On by default so silent recordings never reach the model
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// RMS level (0.0-1.0) above which a 30 ms frame counts as speech
+    #[serde(default = "default_vad_threshold")]
+    pub threshold: f32,
+    /// Audio kept before the first and after the last speech frame
+    #[serde(default = "default_vad_padding")]
+    pub padding_ms: u64,
+    /// Less speech than this is treated as silence (key clicks, bumps)
+    #[serde(default = "default_vad_min_speech")]
+    pub min_speech_ms: u64,
+    /// Pause that ends an utterance in continuous mode
+    #[serde(default = "default_vad_silence")]
+    pub silence_ms: u64,
+}
+
+fn default_vad_threshold() -> f32 {
+    0.01
+}
+fn default_vad_padding() -> u64 {
+    200
+}
+fn default_vad_min_speech() -> u64 {
+    100
+}
+fn default_vad_silence() -> u64 {
+    800
+}
+
+impl Default for VadConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: default_vad_threshold(),
+            padding_ms: default_vad_padding(),
+            min_speech_ms: default_vad_min_speech(),
+            silence_ms: default_vad_silence(),
+        }
+    }
+}
+
 impl Default for Replacements {
     fn default() -> Self {
         Self {
@@ -62,8 +538,20 @@ impl Default for Replacements {
 fn default_hotkey() -> String {
     "AltGr".into()
 }
-fn default_backend() -> String {
-    "local".into()
+fn default_hotkey_backend() -> HotkeyBackend {
+    HotkeyBackend::Rdev
+}
+fn default_cancel_key() -> String {
+    "Escape".into()
+}
+fn default_activation() -> Activation {
+    Activation::Hold
+}
+fn default_hold_threshold() -> u64 {
+    400
+}
+fn default_backend() -> Vec<String> {
+    vec!["local".into()]
 }
 fn default_model_path() -> String {
     "~/.local/share/dictr/models/ggml-base.bin".into()
@@ -71,6 +559,9 @@ fn default_model_path() -> String {
 fn default_api_url() -> String {
     "https://api.openai.com/v1/audio/transcriptions".into()
 }
+fn default_command_timeout() -> u64 {
+    30_000
+}
 fn default_output_mode() -> OutputMode {
     OutputMode::Paste
 }
@@ -80,21 +571,57 @@ fn default_typing_delay() -> u64 {
 fn default_min_duration() -> u64 {
     300
 }
+fn default_task() -> Task {
+    Task::Transcribe
+}
+fn default_streaming_step() -> u64 {
+    2000
+}
+
+fn expand_tilde(path: &mut String) {
+    if path.starts_with('~') {
+        if let Some(home) = std::env::var_os("HOME") {
+            *path = path.replacen('~', &home.to_string_lossy(), 1);
+        }
+    }
+}
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             hotkey: default_hotkey(),
+            hotkey_backend: default_hotkey_backend(),
+            evdev_device: None,
+            cancel_key: default_cancel_key(),
+            activation: default_activation(),
+            hold_threshold_ms: default_hold_threshold(),
             backend: default_backend(),
             model_path: default_model_path(),
+            models: Vec::new(),
             api_key: String::new(),
             api_url: default_api_url(),
+            command: Vec::new(),
+            command_timeout_ms: default_command_timeout(),
+            command_ignore_stderr: false,
             output_mode: default_output_mode(),
             typing_delay_ms: default_typing_delay(),
             min_duration_ms: default_min_duration(),
             device: None,
+            preroll_ms: 0,
+            warm_up: true,
             language: None,
+            languages: Vec::new(),
+            task: default_task(),
             initial_prompt: None,
+            streaming: false,
+            streaming_step_ms: default_streaming_step(),
+            whisper: WhisperConfig::default(),
+            api: ApiConfig::default(),
+            api_audio_format: None,
+            whisper_server: WhisperServerConfig::default(),
+            wyoming: WyomingConfig::default(),
+            filter: FilterConfig::default(),
+            vad: VadConfig::default(),
             replacements: Replacements::default(),
         }
     }
@@ -109,16 +636,22 @@ impl Config {
         } else {
             Config::default()
         };
+        config.resolve_aliases();
         config.resolve_env();
         Ok(config)
     }
 
+    fn resolve_aliases(&mut self) {
+        if let Some(format) = self.api_audio_format.take() {
+            self.api.audio_format = format;
+        }
+    }
+
     fn resolve_env(&mut self) {
-        // Expand tilde in model_path
-        if self.model_path.starts_with('~') {
-            if let Some(home) = std::env::var_os("HOME") {
-                self.model_path = self.model_path.replacen('~', &home.to_string_lossy(), 1);
-            }
+        // Expand tilde in model paths
+        expand_tilde(&mut self.model_path);
+        for rule in &mut self.models {
+            expand_tilde(&mut rule.path);
         }
 
         // Env var fallback for API key
@@ -129,6 +662,23 @@ impl Config {
         }
     }
 
+    /// The `[[models]]` rules that can match, ending in a catch-all:
+    /// `model_path` is only added when no rule leaves `max_secs` unset.
+    pub fn model_rules(&self) -> Vec<ModelRule> {
+        let mut rules = Vec::new();
+        for rule in &self.models {
+            rules.push(rule.clone());
+            if rule.max_secs.is_none() {
+                return rules;
+            }
+        }
+        rules.push(ModelRule {
+            max_secs: None,
+            path: self.model_path.clone(),
+        });
+        rules
+    }
+
     pub fn resolved_model_path(&self) -> PathBuf {
         PathBuf::from(&self.model_path)
     }
@@ -220,7 +770,8 @@ mod tests {
     fn defaults_are_sane() {
         let config = Config::default();
         assert_eq!(config.hotkey, "AltGr");
-        assert_eq!(config.backend, "local");
+        assert_eq!(config.cancel_key, "Escape");
+        assert_eq!(config.backend, ["local"]);
         assert_eq!(config.output_mode, OutputMode::Paste);
         assert_eq!(config.typing_delay_ms, 2);
         assert!(config.api_key.is_empty());
@@ -239,7 +790,7 @@ mod tests {
         "#;
         let config: Config = toml::from_str(toml).unwrap();
         assert_eq!(config.hotkey, "F9");
-        assert_eq!(config.backend, "api");
+        assert_eq!(config.backend, ["api"]);
         assert_eq!(config.model_path, "/tmp/model.bin");
         assert_eq!(config.api_key, "sk-test");
         assert_eq!(config.output_mode, OutputMode::Type);
@@ -251,7 +802,7 @@ mod tests {
         let toml = r#"hotkey = "CapsLock""#;
         let config: Config = toml::from_str(toml).unwrap();
         assert_eq!(config.hotkey, "CapsLock");
-        assert_eq!(config.backend, "local");
+        assert_eq!(config.backend, ["local"]);
         assert_eq!(config.output_mode, OutputMode::Paste);
         assert_eq!(config.typing_delay_ms, 2);
     }
@@ -260,7 +811,7 @@ mod tests {
     fn parse_empty_toml() {
         let config: Config = toml::from_str("").unwrap();
         assert_eq!(config.hotkey, "AltGr");
-        assert_eq!(config.backend, "local");
+        assert_eq!(config.backend, ["local"]);
         assert_eq!(config.output_mode, OutputMode::Paste);
     }
 
@@ -270,6 +821,49 @@ mod tests {
         assert_eq!(config.output_mode, OutputMode::Type);
     }
 
+    #[test]
+    fn parse_hotkey_backend() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.hotkey_backend, HotkeyBackend::Rdev);
+        assert!(config.evdev_device.is_none());
+
+        let toml = r#"
+            hotkey_backend = "evdev"
+            evdev_device = "Keychron"
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.hotkey_backend, HotkeyBackend::Evdev);
+        assert_eq!(config.evdev_device, Some("Keychron".into()));
+    }
+
+    #[test]
+    fn parse_cancel_key() {
+        let config: Config = toml::from_str(r#"cancel_key = "Ctrl+F9""#).unwrap();
+        assert_eq!(config.cancel_key, "Ctrl+F9");
+        let config: Config = toml::from_str(r#"cancel_key = """#).unwrap();
+        assert!(config.cancel_key.is_empty());
+    }
+
+    #[test]
+    fn parse_activation_modes() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.activation, Activation::Hold);
+        assert_eq!(config.hold_threshold_ms, 400);
+
+        let config: Config = toml::from_str(r#"activation = "toggle""#).unwrap();
+        assert_eq!(config.activation, Activation::Toggle);
+
+        let toml = r#"
+            activation = "hybrid"
+            hold_threshold_ms = 250
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.activation, Activation::Hybrid);
+        assert_eq!(config.hold_threshold_ms, 250);
+
+        assert!(toml::from_str::<Config>(r#"activation = "latch""#).is_err());
+    }
+
     #[test]
     fn invalid_output_mode_fails() {
         let result = toml::from_str::<Config>(r#"output_mode = "keyboard""#);
@@ -305,6 +899,19 @@ mod tests {
         assert!(config.model_path.ends_with("/models/test.bin"));
     }
 
+    #[test]
+    fn tilde_expansion_in_model_rules() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [[models]]
+            path = "~/models/base.bin"
+            "#,
+        )
+        .unwrap();
+        config.resolve_env();
+        assert!(!config.models[0].path.starts_with('~'));
+    }
+
     #[test]
     fn absolute_path_not_expanded() {
         let mut config: Config = toml::from_str(r#"model_path = "/opt/models/test.bin""#).unwrap();
@@ -312,6 +919,205 @@ mod tests {
         assert_eq!(config.model_path, "/opt/models/test.bin");
     }
 
+    #[test]
+    fn parse_vad_table() {
+        let config: Config = toml::from_str("").unwrap();
+        assert!(config.vad.enabled);
+        assert_eq!(config.vad.padding_ms, 200);
+
+        let toml = r#"
+            [vad]
+            enabled = false
+            threshold = 0.02
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert!(!config.vad.enabled);
+        assert_eq!(config.vad.threshold, 0.02);
+        assert_eq!(config.vad.min_speech_ms, 100);
+        assert_eq!(config.vad.silence_ms, 800);
+    }
+
+    #[test]
+    fn parse_whisper_table() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.whisper.strategy, Strategy::Greedy);
+        assert_eq!(config.whisper.n_threads, None);
+        assert!(config.whisper.no_context);
+        assert!(!config.whisper.word_timestamps);
+
+        let toml = r#"
+            [whisper]
+            strategy = "beam"
+            beam_size = 8
+            temperature = 0.1
+            n_threads = 2
+            no_context = false
+            suppress_non_speech_tokens = true
+            logprob_thold = -0.5
+            word_timestamps = true
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        let w = &config.whisper;
+        assert_eq!(w.strategy, Strategy::Beam);
+        assert_eq!(w.beam_size, 8);
+        assert_eq!(w.temperature, 0.1);
+        assert_eq!(w.n_threads, Some(2));
+        assert!(!w.no_context);
+        assert!(w.suppress_blank);
+        assert!(w.suppress_non_speech_tokens);
+        assert_eq!(w.entropy_thold, 2.4);
+        assert_eq!(w.logprob_thold, -0.5);
+        assert!(w.word_timestamps);
+    }
+
+    #[test]
+    fn parse_api_table() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.api.model, "whisper-1");
+        assert_eq!(config.api.response_format, ResponseFormat::Json);
+        assert_eq!(config.api.audio_format, AudioFormat::Wav);
+        assert!(config.api.headers.is_empty());
+        assert_eq!(config.api.timeout_ms, 30_000);
+        assert_eq!(config.api.retries, 2);
+
+        let toml = r#"
+            [api]
+            model = "Systran/faster-whisper-small"
+            response_format = "verbose_json"
+            audio_format = "flac"
+            temperature = 0.2
+            headers = { "X-Title" = "dictr" }
+            fields = { "timestamp_granularities[]" = "segment" }
+            connect_timeout_ms = 2000
+            timeout_ms = 10000
+            retries = 0
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        let a = &config.api;
+        assert_eq!(a.model, "Systran/faster-whisper-small");
+        assert_eq!(a.response_format, ResponseFormat::VerboseJson);
+        assert_eq!(a.audio_format, AudioFormat::Flac);
+        assert_eq!(a.temperature, Some(0.2));
+        assert_eq!(a.headers["X-Title"], "dictr");
+        assert_eq!(a.fields["timestamp_granularities[]"], "segment");
+        assert_eq!(a.connect_timeout_ms, 2000);
+        assert_eq!(a.timeout_ms, 10_000);
+        assert_eq!(a.retries, 0);
+        assert_eq!(a.retry_backoff_ms, 500);
+    }
+
+    #[test]
+    fn parse_api_audio_format() {
+        for (name, format) in [
+            ("wav", AudioFormat::Wav),
+            ("flac", AudioFormat::Flac),
+            ("ogg-opus", AudioFormat::OggOpus),
+        ] {
+            let toml = format!("[api]\naudio_format = \"{name}\"");
+            let config: Config = toml::from_str(&toml).unwrap();
+            assert_eq!(config.api.audio_format, format, "{name}");
+        }
+        assert!(toml::from_str::<Config>("[api]\naudio_format = \"mp3\"").is_err());
+
+        let mut config: Config = toml::from_str("api_audio_format = \"flac\"").unwrap();
+        config.resolve_aliases();
+        assert_eq!(config.api.audio_format, AudioFormat::Flac);
+        assert_eq!(config.api_audio_format, None);
+    }
+
+    #[test]
+    fn parse_whisper_server_table() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.whisper_server.url, "http://127.0.0.1:8080/inference");
+        assert_eq!(
+            config.whisper_server.response_format,
+            ResponseFormat::VerboseJson
+        );
+
+        let toml = r#"
+            backend = "whisper-server"
+            [whisper_server]
+            url = "http://workstation:8080/inference"
+            response_format = "json"
+            temperature = 0.0
+            retries = 1
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        let w = &config.whisper_server;
+        assert_eq!(config.backend, ["whisper-server"]);
+        assert_eq!(w.url, "http://workstation:8080/inference");
+        assert_eq!(w.response_format, ResponseFormat::Json);
+        assert_eq!(w.temperature, Some(0.0));
+        assert_eq!(w.timeout_ms, 30_000);
+        assert_eq!(w.retries, 1);
+    }
+
+    #[test]
+    fn parse_wyoming_table() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.wyoming.address, "127.0.0.1:10300");
+        assert_eq!(config.wyoming.model, None);
+
+        let toml = r#"
+            [wyoming]
+            address = "homeassistant.local:10300"
+            model = "small-int8"
+            timeout_ms = 5000
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.wyoming.address, "homeassistant.local:10300");
+        assert_eq!(config.wyoming.model.as_deref(), Some("small-int8"));
+        assert_eq!(config.wyoming.timeout_ms, 5000);
+    }
+
+    #[test]
+    fn parse_command_backend() {
+        let config: Config = toml::from_str("").unwrap();
+        assert!(config.command.is_empty());
+        assert_eq!(config.command_timeout_ms, 30_000);
+        assert!(!config.command_ignore_stderr);
+
+        let toml = r#"
+            backend = "command"
+            command = ["my-asr", "--lang", "{language}"]
+            command_timeout_ms = 60000
+            command_ignore_stderr = true
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.command, ["my-asr", "--lang", "{language}"]);
+        assert_eq!(config.command_timeout_ms, 60_000);
+        assert!(config.command_ignore_stderr);
+    }
+
+    #[test]
+    fn parse_filter_table() {
+        let config: Config = toml::from_str("").unwrap();
+        assert!(config.filter.enabled);
+        assert!(config
+            .filter
+            .phrases
+            .contains(&"thank you for watching".to_string()));
+        assert_eq!(config.filter.max_no_speech_prob, 0.8);
+
+        let toml = r#"
+            [filter]
+            min_avg_logprob = -0.8
+            max_no_speech_prob = 0.5
+            phrases = ["bye"]
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert!(config.filter.enabled);
+        assert_eq!(config.filter.min_avg_logprob, -0.8);
+        assert_eq!(config.filter.max_no_speech_prob, 0.5);
+        assert_eq!(config.filter.phrases, vec!["bye".to_string()]);
+    }
+
+    #[test]
+    fn reject_unknown_strategy() {
+        let result = toml::from_str::<Config>("[whisper]\nstrategy = \"sampling\"");
+        assert!(result.is_err());
+    }
+
     #[test]
     fn replacements_basic() {
         let toml = r#"
@@ -433,14 +1239,105 @@ mod tests {
         let toml = r#"
             api_url = "http://localhost:8080/v1/transcriptions"
             min_duration_ms = 500
+            preroll_ms = 250
+            streaming = true
+            streaming_step_ms = 1500
+            warm_up = false
             initial_prompt = "NixOS, Rust"
             language = "en"
+            languages = ["en", "fr", "de"]
+            task = "translate"
         "#;
         let config: Config = toml::from_str(toml).unwrap();
         assert_eq!(config.api_url, "http://localhost:8080/v1/transcriptions");
         assert_eq!(config.min_duration_ms, 500);
+        assert_eq!(config.preroll_ms, 250);
+        assert!(config.streaming);
+        assert_eq!(config.streaming_step_ms, 1500);
+        assert!(!config.warm_up);
         assert_eq!(config.initial_prompt, Some("NixOS, Rust".into()));
         assert_eq!(config.language, Some("en".into()));
+        assert_eq!(config.languages, vec!["en", "fr", "de"]);
+        assert_eq!(config.task, Task::Translate);
+    }
+
+    #[test]
+    fn parse_backend_list() {
+        let config: Config = toml::from_str(r#"backend = ["api", "local"]"#).unwrap();
+        assert_eq!(config.backend, ["api", "local"]);
+        assert!(toml::from_str::<Config>("backend = 1").is_err());
+    }
+
+    #[test]
+    fn parse_model_rules() {
+        let config: Config = toml::from_str(
+            r#"
+            model_path = "default.bin"
+
+            [[models]]
+            max_secs = 5
+            path = "base.bin"
+
+            [[models]]
+            path = "medium.bin"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.model_rules(),
+            [
+                ModelRule {
+                    max_secs: Some(5.0),
+                    path: "base.bin".into()
+                },
+                ModelRule {
+                    max_secs: None,
+                    path: "medium.bin".into()
+                },
+            ]
+        );
+        assert!(Config::default().models.is_empty());
+    }
+
+    #[test]
+    fn model_path_only_backs_up_bounded_rules() {
+        let config: Config = toml::from_str(
+            r#"
+            model_path = "default.bin"
+
+            [[models]]
+            max_secs = 5
+            path = "base.bin"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.model_rules().last(),
+            Some(&ModelRule {
+                max_secs: None,
+                path: "default.bin".into()
+            })
+        );
+
+        // Rules after a catch-all are never reached
+        let config: Config = toml::from_str(
+            r#"
+            [[models]]
+            path = "medium.bin"
+
+            [[models]]
+            max_secs = 5
+            path = "base.bin"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.model_rules(),
+            [ModelRule {
+                max_secs: None,
+                path: "medium.bin".into()
+            }]
+        );
     }
 
     #[test]
@@ -451,6 +1348,11 @@ mod tests {
             "https://api.openai.com/v1/audio/transcriptions"
         );
         assert_eq!(config.min_duration_ms, 300);
+        assert_eq!(config.preroll_ms, 0);
+        assert!(!config.streaming);
+        assert_eq!(config.streaming_step_ms, 2000);
+        assert!(config.warm_up);
+        assert_eq!(config.task, Task::Transcribe);
         assert!(config.initial_prompt.is_none());
         assert!(config.language.is_none());
     }
@@ -0,0 +1,289 @@
+use anyhow::{bail, Context, Result};
+use rdev::{EventType, Key};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+
+const EV_KEY: u16 = 0x01;
+const KEY_SPACE: u16 = 57;
+const INPUT_DIR: &str = "/dev/input";
+const SYSFS_INPUT_DIR: &str = "/sys/class/input";
+
+/// Start reading key events from `/dev/input/event*`. Works without X11
+/// (Wayland, TTY) but needs read access to the devices, usually via the
+/// `input` group. `filter` matches a device path or a substring of its name;
+/// without it every device that has a space bar is used.
+pub fn start_listener<F>(filter: Option<&str>, mut on_event: F) -> Result<thread::JoinHandle<()>>
+where
+    F: FnMut(EventType) + Send + 'static,
+{
+    let devices = find_devices(filter)?;
+    let (tx, rx) = mpsc::channel();
+
+    let mut opened = 0;
+    for (path, name) in devices {
+        let file = match File::open(&path) {
+            Ok(f) => f,
+            Err(e) => {
+                eprintln!("warning: cannot open {} ({name}): {e}", path.display());
+                continue;
+            }
+        };
+        opened += 1;
+        let tx = tx.clone();
+        thread::spawn(move || {
+            let result = read_events(file, |event_type| {
+                let _ = tx.send(event_type);
+            });
+            if let Err(e) = result {
+                eprintln!("evdev read error on {}: {e}", path.display());
+            }
+        });
+    }
+    drop(tx);
+
+    if opened == 0 {
+        bail!("no readable keyboard device in {INPUT_DIR} (is your user in the 'input' group?)");
+    }
+
+    // Funnel all devices through one thread so chords can span keyboards
+    let handle = thread::spawn(move || {
+        for event_type in rx {
+            on_event(event_type);
+        }
+    });
+    Ok(handle)
+}
+
+/// Read raw `input_event` structs until EOF, reporting key presses and releases.
+/// Autorepeat is reported as a press, which the debouncer already suppresses.
+fn read_events<R: Read>(mut reader: R, mut on_event: impl FnMut(EventType)) -> Result<()> {
+    let size = std::mem::size_of::<libc::input_event>();
+    let mut buf = vec![0u8; size];
+    loop {
+        match reader.read_exact(&mut buf) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+        // SAFETY: buf holds exactly one input_event worth of bytes, and
+        // input_event is plain old data.
+        let ev: libc::input_event =
+            unsafe { std::ptr::read_unaligned(buf.as_ptr() as *const libc::input_event) };
+        if let Some(event_type) = event_type(&ev) {
+            on_event(event_type);
+        }
+    }
+}
+
+fn event_type(ev: &libc::input_event) -> Option<EventType> {
+    if ev.type_ != EV_KEY {
+        return None;
+    }
+    let key = key_from_code(ev.code)?;
+    match ev.value {
+        0 => Some(EventType::KeyRelease(key)),
+        1 | 2 => Some(EventType::KeyPress(key)),
+        _ => None,
+    }
+}
+
+/// Map Linux `KEY_*` codes to the rdev keys that `hotkey::parse_key` knows.
+fn key_from_code(code: u16) -> Option<Key> {
+    let key = match code {
+        1 => Key::Escape,
+        29 => Key::ControlLeft,
+        42 => Key::ShiftLeft,
+        54 => Key::ShiftRight,
+        56 => Key::Alt,
+        57 => Key::Space,
+        58 => Key::CapsLock,
+        59 => Key::F1,
+        60 => Key::F2,
+        61 => Key::F3,
+        62 => Key::F4,
+        63 => Key::F5,
+        64 => Key::F6,
+        65 => Key::F7,
+        66 => Key::F8,
+        67 => Key::F9,
+        68 => Key::F10,
+        87 => Key::F11,
+        88 => Key::F12,
+        97 => Key::ControlRight,
+        100 => Key::AltGr,
+        125 => Key::MetaLeft,
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Returns (path, name) of the event devices to listen on.
+fn find_devices(filter: Option<&str>) -> Result<Vec<(PathBuf, String)>> {
+    let entries =
+        std::fs::read_dir(INPUT_DIR).with_context(|| format!("failed to read {INPUT_DIR}"))?;
+    let mut devices = Vec::new();
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+        if !file_name.starts_with("event") {
+            continue;
+        }
+        let sys = Path::new(SYSFS_INPUT_DIR).join(&*file_name).join("device");
+        let name = std::fs::read_to_string(sys.join("name"))
+            .map(|n| n.trim().to_string())
+            .unwrap_or_default();
+        let path = entry.path();
+        let selected = match filter {
+            Some(f) => matches_filter(&path, &name, f),
+            None => std::fs::read_to_string(sys.join("capabilities/key"))
+                .map(|caps| has_key(&caps, KEY_SPACE))
+                .unwrap_or(false),
+        };
+        if selected {
+            devices.push((path, name));
+        }
+    }
+    devices.sort();
+    if devices.is_empty() {
+        match filter {
+            Some(f) => bail!("no input device matching '{f}' in {INPUT_DIR}"),
+            None => bail!("no keyboard found in {INPUT_DIR}"),
+        }
+    }
+    Ok(devices)
+}
+
+fn matches_filter(path: &Path, name: &str, filter: &str) -> bool {
+    path == Path::new(filter) || name.to_lowercase().contains(&filter.to_lowercase())
+}
+
+/// Check a key code in a sysfs capability bitmap: space-separated hex words,
+/// most significant word first.
+fn has_key(caps: &str, code: u16) -> bool {
+    let bits = usize::BITS as usize;
+    let words: Vec<&str> = caps.split_whitespace().collect();
+    let idx = code as usize / bits;
+    if idx >= words.len() {
+        return false;
+    }
+    let word = words[words.len() - 1 - idx];
+    usize::from_str_radix(word, 16)
+        .map(|w| w & (1 << (code as usize % bits)) != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn raw_event(type_: u16, code: u16, value: i32) -> Vec<u8> {
+        let ev = libc::input_event {
+            time: libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            type_,
+            code,
+            value,
+        };
+        let size = std::mem::size_of::<libc::input_event>();
+        // SAFETY: input_event is plain old data
+        unsafe { std::slice::from_raw_parts(&ev as *const _ as *const u8, size) }.to_vec()
+    }
+
+    fn read_all(bytes: Vec<u8>) -> Vec<EventType> {
+        let mut events = Vec::new();
+        read_events(Cursor::new(bytes), |e| events.push(e)).unwrap();
+        events
+    }
+
+    #[test]
+    fn key_codes_map_to_rdev_keys() {
+        assert_eq!(key_from_code(100), Some(Key::AltGr));
+        assert_eq!(key_from_code(29), Some(Key::ControlLeft));
+        assert_eq!(key_from_code(57), Some(Key::Space));
+        assert_eq!(key_from_code(67), Some(Key::F9));
+        assert_eq!(key_from_code(88), Some(Key::F12));
+        assert_eq!(key_from_code(30), None); // KEY_A is not a hotkey
+    }
+
+    #[test]
+    fn read_press_repeat_release() {
+        let mut bytes = raw_event(EV_KEY, 100, 1);
+        bytes.extend(raw_event(0, 0, 0)); // EV_SYN
+        bytes.extend(raw_event(EV_KEY, 100, 2));
+        bytes.extend(raw_event(EV_KEY, 100, 0));
+        assert_eq!(
+            read_all(bytes),
+            vec![
+                EventType::KeyPress(Key::AltGr),
+                EventType::KeyPress(Key::AltGr),
+                EventType::KeyRelease(Key::AltGr),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_ignores_non_key_events_and_unknown_codes() {
+        let mut bytes = raw_event(0x04, 4, 458_756); // EV_MSC scan code
+        bytes.extend(raw_event(EV_KEY, 30, 1)); // KEY_A
+        bytes.extend(raw_event(0x02, 0, 5)); // EV_REL mouse motion
+        assert!(read_all(bytes).is_empty());
+    }
+
+    #[test]
+    fn read_chord_sequence_drives_debouncer_input() {
+        let mut bytes = raw_event(EV_KEY, 29, 1);
+        bytes.extend(raw_event(EV_KEY, 42, 1));
+        bytes.extend(raw_event(EV_KEY, 57, 1));
+        bytes.extend(raw_event(EV_KEY, 42, 0));
+        assert_eq!(
+            read_all(bytes),
+            vec![
+                EventType::KeyPress(Key::ControlLeft),
+                EventType::KeyPress(Key::ShiftLeft),
+                EventType::KeyPress(Key::Space),
+                EventType::KeyRelease(Key::ShiftLeft),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_truncated_event_stops_cleanly() {
+        let mut bytes = raw_event(EV_KEY, 100, 1);
+        bytes.extend_from_slice(&[0u8; 5]);
+        assert_eq!(read_all(bytes), vec![EventType::KeyPress(Key::AltGr)]);
+    }
+
+    #[test]
+    fn filter_matches_path_or_name() {
+        let path = Path::new("/dev/input/event3");
+        assert!(matches_filter(
+            path,
+            "AT Translated Set 2 keyboard",
+            "/dev/input/event3"
+        ));
+        assert!(matches_filter(
+            path,
+            "AT Translated Set 2 keyboard",
+            "translated"
+        ));
+        assert!(!matches_filter(path, "Logitech USB Receiver", "keychron"));
+    }
+
+    #[test]
+    fn capability_bitmap_lookup() {
+        // Typical keyboard: low word has bits for Esc..F12 including space (57)
+        let keyboard = "120013 0 0 0 0 0 0 feffffdfffcfffff fffffffffffffffe";
+        assert!(has_key(keyboard, KEY_SPACE));
+        assert!(has_key(keyboard, 1));
+        // Mouse: only BTN_* bits in a high word
+        let mouse = "1f0000 0 0 0 0";
+        assert!(!has_key(mouse, KEY_SPACE));
+        assert!(!has_key("", KEY_SPACE));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::config::FilterConfig;
+use crate::transcribe::Segment;
+
+/// Drops transcribed segments that are likely hallucinations: low-confidence
+/// decodes, non-speech annotations like "[Music]" and known filler phrases
+/// whisper produces on near-silent audio.
+#[derive(Clone)]
+pub struct SegmentFilter {
+    config: FilterConfig,
+    phrases: Vec<String>,
+    verbose: bool,
+}
+
+impl SegmentFilter {
+    pub fn new(config: &FilterConfig, verbose: bool) -> Self {
+        Self {
+            config: config.clone(),
+            phrases: config.phrases.iter().map(|p| normalize(p)).collect(),
+            verbose,
+        }
+    }
+
+    /// Keep the segments that pass, logging dropped ones in verbose mode.
+    pub fn apply(&self, segments: Vec<Segment>) -> Vec<Segment> {
+        segments
+            .into_iter()
+            .filter(|segment| self.passes(segment))
+            .collect()
+    }
+
+    /// Whether a segment passes, without logging.
+    pub fn keep(&self, segment: &Segment) -> bool {
+        self.reject_reason(segment).is_none()
+    }
+
+    fn passes(&self, segment: &Segment) -> bool {
+        match self.reject_reason(segment) {
+            Some(reason) => {
+                if self.verbose {
+                    eprint!("(dropped \"{}\": {reason}) ", segment.text);
+                }
+                false
+            }
+            None => true,
+        }
+    }
+
+    fn reject_reason(&self, segment: &Segment) -> Option<String> {
+        let text = &segment.text;
+        if !self.config.enabled || text.trim().is_empty() {
+            return None;
+        }
+        if let Some(prob) = segment.no_speech_prob {
+            if prob > self.config.max_no_speech_prob {
+                return Some(format!(
+                    "no-speech probability {prob:.2} > {:.2}",
+                    self.config.max_no_speech_prob
+                ));
+            }
+        }
+        if let Some(logprob) = segment.avg_logprob {
+            if logprob < self.config.min_avg_logprob {
+                return Some(format!(
+                    "avg logprob {logprob:.2} < {:.2}",
+                    self.config.min_avg_logprob
+                ));
+            }
+        }
+        if is_annotation(text) {
+            return Some("non-speech annotation".into());
+        }
+        let normalized = normalize(text);
+        if self.phrases.contains(&normalized) {
+            return Some("known hallucination".into());
+        }
+        None
+    }
+}
+
+/// "[Music]", "(applause)", "*laughs*", "♪ ♪" and the like.
+fn is_annotation(text: &str) -> bool {
+    let t = text.trim();
+    let enclosed = |open, close| t.len() >= 2 && t.starts_with(open) && t.ends_with(close);
+    enclosed('[', ']')
+        || enclosed('(', ')')
+        || enclosed('*', '*')
+        || t.chars()
+            .all(|c| c == '♪' || c.is_whitespace() || c.is_ascii_punctuation())
+}
+
+/// Lowercase, drop surrounding punctuation and collapse whitespace.
+fn normalize(text: &str) -> String {
+    let trimmed = text.trim_matches(|c: char| !c.is_alphanumeric());
+    trimmed
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(text: &str, avg_logprob: Option<f32>) -> Segment {
+        Segment {
+            text: text.into(),
+            start_ms: 0,
+            end_ms: 0,
+            avg_logprob,
+            no_speech_prob: None,
+            words: Vec::new(),
+        }
+    }
+
+    fn filter() -> SegmentFilter {
+        SegmentFilter::new(&FilterConfig::default(), false)
+    }
+
+    fn kept(segments: Vec<Segment>) -> Vec<String> {
+        filter()
+            .apply(segments)
+            .into_iter()
+            .map(|s| s.text)
+            .collect()
+    }
+
+    #[test]
+    fn drops_known_phrases_ignoring_case_and_punctuation() {
+        let segments = vec![
+            segment("Fix the build.", None),
+            segment(" Thank you for watching! ", None),
+            segment("THANKS FOR WATCHING", None),
+        ];
+        assert_eq!(kept(segments), vec!["Fix the build."]);
+    }
+
+    #[test]
+    fn phrase_must_be_the_whole_segment() {
+        let segments = vec![segment("Thank you for watching the logs for me.", None)];
+        assert_eq!(kept(segments).len(), 1);
+    }
+
+    #[test]
+    fn drops_annotations() {
+        let segments = vec![
+            segment("[Music]", None),
+            segment("(applause)", None),
+            segment("*laughs*", None),
+            segment("♪ ♪", None),
+            segment("[BLANK_AUDIO]", None),
+            segment("Run the tests (all of them)", None),
+        ];
+        assert_eq!(kept(segments), vec!["Run the tests (all of them)"]);
+    }
+
+    #[test]
+    fn drops_low_confidence() {
+        let segments = vec![
+            segment("confident", Some(-0.2)),
+            segment("mumble", Some(-1.4)),
+            segment("unknown", None),
+        ];
+        assert_eq!(kept(segments), vec!["confident", "unknown"]);
+    }
+
+    #[test]
+    fn drops_likely_silence() {
+        let with_prob = |text, prob| Segment {
+            no_speech_prob: Some(prob),
+            ..segment(text, Some(-0.2))
+        };
+        let segments = vec![
+            with_prob("Ship it.", 0.05),
+            with_prob("Thanks.", 0.95),
+            with_prob("borderline", 0.8),
+        ];
+        assert_eq!(kept(segments), vec!["Ship it.", "borderline"]);
+        assert_eq!(
+            filter().reject_reason(&with_prob("Thanks.", 0.95)).unwrap(),
+            "no-speech probability 0.95 > 0.80"
+        );
+    }
+
+    #[test]
+    fn disabled_keeps_everything() {
+        let config = FilterConfig {
+            enabled: false,
+            ..FilterConfig::default()
+        };
+        let filter = SegmentFilter::new(&config, false);
+        assert!(filter.keep(&segment("[Music]", Some(-3.0))));
+        assert!(filter.keep(&segment("Thanks for watching.", None)));
+    }
+
+    #[test]
+    fn custom_phrases_replace_defaults() {
+        let config = FilterConfig {
+            phrases: vec!["Bye.".into()],
+            ..FilterConfig::default()
+        };
+        let filter = SegmentFilter::new(&config, false);
+        assert!(!filter.keep(&segment("bye", None)));
+        assert!(filter.keep(&segment("Thanks for watching", None)));
+    }
+}
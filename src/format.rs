@@ -0,0 +1,198 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::transcribe::{Segment, Transcript};
+
+/// How `--file` prints its transcription.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Plain text
+    Text,
+    /// SubRip subtitles
+    Srt,
+    /// WebVTT subtitles, with per-word timing when word timestamps are on
+    Vtt,
+    /// Segments with timestamps (and words when enabled)
+    Json,
+}
+
+pub fn render(format: OutputFormat, transcript: &Transcript) -> Result<String> {
+    Ok(match format {
+        OutputFormat::Text => transcript.text(),
+        OutputFormat::Srt => srt(&transcript.segments),
+        OutputFormat::Vtt => vtt(&transcript.segments),
+        OutputFormat::Json => json(transcript)?,
+    })
+}
+
+fn srt(segments: &[Segment]) -> String {
+    let mut out = String::new();
+    for (i, segment) in cues(segments).enumerate() {
+        out.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            i + 1,
+            timestamp(segment.start_ms, ','),
+            timestamp(segment.end_ms, ','),
+            segment.text
+        ));
+    }
+    out
+}
+
+fn vtt(segments: &[Segment]) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for segment in cues(segments) {
+        out.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            timestamp(segment.start_ms, '.'),
+            timestamp(segment.end_ms, '.'),
+            vtt_payload(segment)
+        ));
+    }
+    out
+}
+
+/// Cue text; with word timing, every word after the first gets an inline
+/// `<hh:mm:ss.mmm>` tag so players can highlight it as it is spoken.
+fn vtt_payload(segment: &Segment) -> String {
+    if segment.words.is_empty() {
+        return segment.text.clone();
+    }
+    segment
+        .words
+        .iter()
+        .enumerate()
+        .map(|(i, word)| match i {
+            0 => word.text.clone(),
+            _ => format!("<{}>{}", timestamp(word.start_ms, '.'), word.text),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn json(transcript: &Transcript) -> Result<String> {
+    #[derive(Serialize)]
+    struct Output<'a> {
+        text: String,
+        #[serde(flatten)]
+        transcript: &'a Transcript,
+    }
+    let output = Output {
+        text: transcript.text(),
+        transcript,
+    };
+    Ok(serde_json::to_string_pretty(&output)?)
+}
+
+fn cues(segments: &[Segment]) -> impl Iterator<Item = &Segment> {
+    segments.iter().filter(|s| !s.text.is_empty())
+}
+
+/// `hh:mm:ss,mmm` (SRT) or `hh:mm:ss.mmm` (WebVTT)
+fn timestamp(ms: u64, separator: char) -> String {
+    format!(
+        "{:02}:{:02}:{:02}{separator}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcribe::Word;
+
+    fn segment(text: &str, start_ms: u64, end_ms: u64) -> Segment {
+        Segment {
+            text: text.into(),
+            start_ms,
+            end_ms,
+            avg_logprob: None,
+            no_speech_prob: None,
+            words: Vec::new(),
+        }
+    }
+
+    fn word(text: &str, start_ms: u64, end_ms: u64) -> Word {
+        Word {
+            text: text.into(),
+            start_ms,
+            end_ms,
+        }
+    }
+
+    fn sample() -> Transcript {
+        Transcript {
+            segments: vec![
+                segment("Hello there.", 0, 1500),
+                segment("", 1500, 2000),
+                segment("Next item.", 3_723_004, 3_725_000),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(0, ','), "00:00:00,000");
+        assert_eq!(timestamp(3_723_004, ','), "01:02:03,004");
+        assert_eq!(timestamp(59_999, '.'), "00:00:59.999");
+    }
+
+    #[test]
+    fn text_joins_segments() {
+        assert_eq!(
+            render(OutputFormat::Text, &sample()).unwrap(),
+            "Hello there. Next item."
+        );
+    }
+
+    #[test]
+    fn srt_numbers_cues_and_skips_empty_segments() {
+        assert_eq!(
+            render(OutputFormat::Srt, &sample()).unwrap(),
+            "1\n00:00:00,000 --> 00:00:01,500\nHello there.\n\n\
+             2\n01:02:03,004 --> 01:02:05,000\nNext item.\n\n"
+        );
+    }
+
+    #[test]
+    fn vtt_has_header_and_dot_separator() {
+        assert_eq!(
+            render(OutputFormat::Vtt, &sample()).unwrap(),
+            "WEBVTT\n\n00:00:00.000 --> 00:00:01.500\nHello there.\n\n\
+             01:02:03.004 --> 01:02:05.000\nNext item.\n\n"
+        );
+    }
+
+    #[test]
+    fn vtt_tags_words_with_their_start() {
+        let mut s = segment("Hello there.", 0, 1500);
+        s.words = vec![word("Hello", 0, 600), word("there.", 700, 1500)];
+        assert_eq!(vtt_payload(&s), "Hello <00:00:00.700>there.");
+    }
+
+    fn parse_json(transcript: &Transcript) -> serde_json::Value {
+        serde_json::from_str(&render(OutputFormat::Json, transcript).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn json_has_text_and_segments() {
+        let mut transcript = sample();
+        transcript.language = Some("fr".into());
+        transcript.segments[0].words = vec![word("Hello", 0, 600)];
+        transcript.segments[0].avg_logprob = Some(-0.25);
+        let value = parse_json(&transcript);
+        assert_eq!(value["text"], "Hello there. Next item.");
+        assert_eq!(value["language"], "fr");
+        assert_eq!(value["segments"][0]["end_ms"], 1500);
+        assert_eq!(value["segments"][0]["avg_logprob"], -0.25);
+        assert_eq!(value["segments"][0]["words"][0]["text"], "Hello");
+        // Optional fields are omitted rather than null
+        assert!(value["segments"][2].get("words").is_none());
+        assert!(value["segments"][2].get("avg_logprob").is_none());
+        assert!(parse_json(&sample()).get("language").is_none());
+    }
+}
@@ -2,35 +2,54 @@ use anyhow::{bail, Result};
 use rdev::{listen, Event, EventType, Key};
 use std::sync::mpsc;
 use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::{Activation, Config, HotkeyBackend};
+use crate::evdev;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HotkeyEvent {
     Pressed,
     Released,
+    Cancel,
 }
 
+/// Tracks a hotkey chord (one or more keys). Emits `Pressed` once every key
+/// of the chord is held, in any order, and `Released` as soon as any of them
+/// is let go.
 struct Debouncer {
-    target_key: Key,
+    chord: Vec<Key>,
+    held: Vec<bool>,
     is_pressed: bool,
 }
 
 impl Debouncer {
-    fn new(target_key: Key) -> Self {
+    fn new(chord: Vec<Key>) -> Self {
+        let held = vec![false; chord.len()];
         Self {
-            target_key,
+            chord,
+            held,
             is_pressed: false,
         }
     }
 
     fn handle(&mut self, event_type: EventType) -> Option<HotkeyEvent> {
         match event_type {
-            EventType::KeyPress(key) if key == self.target_key && !self.is_pressed => {
-                self.is_pressed = true;
-                return Some(HotkeyEvent::Pressed);
+            EventType::KeyPress(key) => {
+                let idx = self.chord.iter().position(|k| *k == key)?;
+                self.held[idx] = true;
+                if !self.is_pressed && self.held.iter().all(|h| *h) {
+                    self.is_pressed = true;
+                    return Some(HotkeyEvent::Pressed);
+                }
             }
-            EventType::KeyRelease(key) if key == self.target_key && self.is_pressed => {
-                self.is_pressed = false;
-                return Some(HotkeyEvent::Released);
+            EventType::KeyRelease(key) => {
+                let idx = self.chord.iter().position(|k| *k == key)?;
+                self.held[idx] = false;
+                if self.is_pressed {
+                    self.is_pressed = false;
+                    return Some(HotkeyEvent::Released);
+                }
             }
             _ => {}
         }
@@ -38,27 +57,165 @@ impl Debouncer {
     }
 }
 
-pub fn start_listener(
-    hotkey_name: &str,
-    tx: mpsc::Sender<HotkeyEvent>,
-) -> Result<thread::JoinHandle<()>> {
-    let target_key = parse_key(hotkey_name)?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordAction {
+    Start,
+    Stop,
+    Cancel,
+}
 
-    let handle = thread::spawn(move || {
-        let mut debouncer = Debouncer::new(target_key);
+/// Maps debounced hotkey events to recording start/stop according to the
+/// configured activation mode.
+pub struct Activator {
+    mode: Activation,
+    hold_threshold: Duration,
+    recording: bool,
+    /// Set when the current press started a recording (hybrid mode only)
+    pressed_at: Option<Instant>,
+}
 
-        let callback = move |event: Event| {
-            if let Some(hotkey_event) = debouncer.handle(event.event_type) {
-                let _ = tx.send(hotkey_event);
+impl Activator {
+    pub fn new(mode: Activation, hold_threshold: Duration) -> Self {
+        Self {
+            mode,
+            hold_threshold,
+            recording: false,
+            pressed_at: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    pub fn handle(&mut self, event: HotkeyEvent, now: Instant) -> Option<RecordAction> {
+        match (self.mode, event) {
+            (_, HotkeyEvent::Cancel) => {
+                if !self.recording {
+                    return None;
+                }
+                self.recording = false;
+                self.pressed_at = None;
+                Some(RecordAction::Cancel)
+            }
+            (Activation::Hold, HotkeyEvent::Pressed) => self.start(),
+            (Activation::Hold, HotkeyEvent::Released) => self.stop(),
+            (Activation::Toggle, HotkeyEvent::Pressed) => {
+                if self.recording {
+                    self.stop()
+                } else {
+                    self.start()
+                }
+            }
+            (Activation::Toggle, HotkeyEvent::Released) => None,
+            (Activation::Hybrid, HotkeyEvent::Pressed) => {
+                if self.recording {
+                    self.stop()
+                } else {
+                    self.pressed_at = Some(now);
+                    self.start()
+                }
             }
+            (Activation::Hybrid, HotkeyEvent::Released) => {
+                // Only the press that started recording decides between
+                // latching (short tap) and push-to-talk (long hold)
+                let pressed_at = self.pressed_at.take()?;
+                if now.duration_since(pressed_at) >= self.hold_threshold {
+                    self.stop()
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    fn start(&mut self) -> Option<RecordAction> {
+        if self.recording {
+            return None;
+        }
+        self.recording = true;
+        Some(RecordAction::Start)
+    }
+
+    fn stop(&mut self) -> Option<RecordAction> {
+        if !self.recording {
+            return None;
+        }
+        self.recording = false;
+        self.pressed_at = None;
+        Some(RecordAction::Stop)
+    }
+}
+
+/// Routes raw key events through the hotkey and cancel-key debouncers.
+struct KeyRouter {
+    hotkey: Debouncer,
+    cancel: Option<Debouncer>,
+}
+
+impl KeyRouter {
+    /// An empty `cancel_key` disables cancelling.
+    fn new(hotkey_name: &str, cancel_key: &str) -> Result<Self> {
+        let hotkey = Debouncer::new(parse_hotkey(hotkey_name)?);
+        let cancel = if cancel_key.is_empty() {
+            None
+        } else {
+            Some(Debouncer::new(parse_hotkey(cancel_key)?))
         };
+        Ok(Self { hotkey, cancel })
+    }
+
+    fn handle(&mut self, event_type: EventType, tx: &mpsc::Sender<HotkeyEvent>) {
+        if let Some(cancel) = self.cancel.as_mut() {
+            if cancel.handle(event_type) == Some(HotkeyEvent::Pressed) {
+                let _ = tx.send(HotkeyEvent::Cancel);
+            }
+        }
+        if let Some(hotkey_event) = self.hotkey.handle(event_type) {
+            let _ = tx.send(hotkey_event);
+        }
+    }
+}
+
+pub fn start_listener(
+    config: &Config,
+    tx: mpsc::Sender<HotkeyEvent>,
+) -> Result<thread::JoinHandle<()>> {
+    let mut router = KeyRouter::new(&config.hotkey, &config.cancel_key)?;
 
-        if let Err(e) = listen(callback) {
-            eprintln!("hotkey listener error: {:?}", e);
+    match config.hotkey_backend {
+        HotkeyBackend::Rdev => {
+            let handle = thread::spawn(move || {
+                let callback = move |event: Event| router.handle(event.event_type, &tx);
+                if let Err(e) = listen(callback) {
+                    eprintln!("hotkey listener error: {:?}", e);
+                }
+            });
+            Ok(handle)
+        }
+        HotkeyBackend::Evdev => {
+            evdev::start_listener(config.evdev_device.as_deref(), move |event_type| {
+                router.handle(event_type, &tx)
+            })
         }
-    });
+    }
+}
 
-    Ok(handle)
+/// Parse a hotkey spec such as `AltGr` or `Ctrl+Shift+Space` into its keys.
+fn parse_hotkey(spec: &str) -> Result<Vec<Key>> {
+    let mut chord = Vec::new();
+    for part in spec.split('+') {
+        let part = part.trim();
+        if part.is_empty() {
+            bail!("invalid hotkey: {spec}");
+        }
+        let key = parse_key(part)?;
+        if chord.contains(&key) {
+            bail!("duplicate key in hotkey: {spec}");
+        }
+        chord.push(key);
+    }
+    Ok(chord)
 }
 
 fn parse_key(name: &str) -> Result<Key> {
@@ -130,9 +287,35 @@ mod tests {
         assert!(parse_key("").is_err());
     }
 
+    #[test]
+    fn parse_single_key_hotkey() {
+        assert_eq!(parse_hotkey("AltGr").unwrap(), vec![Key::AltGr]);
+        assert_eq!(parse_hotkey("F9").unwrap(), vec![Key::F9]);
+    }
+
+    #[test]
+    fn parse_chord_hotkey() {
+        assert_eq!(
+            parse_hotkey("Ctrl+Shift+Space").unwrap(),
+            vec![Key::ControlLeft, Key::ShiftLeft, Key::Space]
+        );
+        assert_eq!(
+            parse_hotkey("super + f9").unwrap(),
+            vec![Key::MetaLeft, Key::F9]
+        );
+    }
+
+    #[test]
+    fn parse_invalid_chord_fails() {
+        assert!(parse_hotkey("Ctrl+").is_err());
+        assert!(parse_hotkey("+Space").is_err());
+        assert!(parse_hotkey("Ctrl+Nope").is_err());
+        assert!(parse_hotkey("Ctrl+ctrl").is_err());
+    }
+
     #[test]
     fn debounce_repeated_press_emits_once() {
-        let mut d = Debouncer::new(Key::AltGr);
+        let mut d = Debouncer::new(vec![Key::AltGr]);
         // First press emits
         assert_eq!(
             d.handle(EventType::KeyPress(Key::AltGr)),
@@ -151,20 +334,20 @@ mod tests {
 
     #[test]
     fn debounce_release_without_press_ignored() {
-        let mut d = Debouncer::new(Key::AltGr);
+        let mut d = Debouncer::new(vec![Key::AltGr]);
         assert_eq!(d.handle(EventType::KeyRelease(Key::AltGr)), None);
     }
 
     #[test]
     fn debounce_ignores_other_keys() {
-        let mut d = Debouncer::new(Key::AltGr);
+        let mut d = Debouncer::new(vec![Key::AltGr]);
         assert_eq!(d.handle(EventType::KeyPress(Key::Space)), None);
         assert_eq!(d.handle(EventType::KeyRelease(Key::Space)), None);
     }
 
     #[test]
     fn debounce_press_release_press_cycle() {
-        let mut d = Debouncer::new(Key::F9);
+        let mut d = Debouncer::new(vec![Key::F9]);
         assert_eq!(
             d.handle(EventType::KeyPress(Key::F9)),
             Some(HotkeyEvent::Pressed)
@@ -182,4 +365,202 @@ mod tests {
             Some(HotkeyEvent::Released)
         );
     }
+
+    #[test]
+    fn debounce_chord_fires_when_fully_held() {
+        let mut d = Debouncer::new(vec![Key::ControlLeft, Key::ShiftLeft, Key::Space]);
+        assert_eq!(d.handle(EventType::KeyPress(Key::ControlLeft)), None);
+        assert_eq!(d.handle(EventType::KeyPress(Key::ShiftLeft)), None);
+        assert_eq!(
+            d.handle(EventType::KeyPress(Key::Space)),
+            Some(HotkeyEvent::Pressed)
+        );
+        // Key repeat on any chord key is suppressed
+        assert_eq!(d.handle(EventType::KeyPress(Key::Space)), None);
+        assert_eq!(d.handle(EventType::KeyPress(Key::ControlLeft)), None);
+    }
+
+    #[test]
+    fn debounce_chord_any_order() {
+        let mut d = Debouncer::new(vec![Key::ControlLeft, Key::ShiftLeft, Key::Space]);
+        assert_eq!(d.handle(EventType::KeyPress(Key::Space)), None);
+        assert_eq!(d.handle(EventType::KeyPress(Key::ShiftLeft)), None);
+        assert_eq!(
+            d.handle(EventType::KeyPress(Key::ControlLeft)),
+            Some(HotkeyEvent::Pressed)
+        );
+    }
+
+    #[test]
+    fn debounce_chord_partial_press_then_release_is_silent() {
+        let mut d = Debouncer::new(vec![Key::ControlLeft, Key::Space]);
+        assert_eq!(d.handle(EventType::KeyPress(Key::ControlLeft)), None);
+        assert_eq!(d.handle(EventType::KeyRelease(Key::ControlLeft)), None);
+        assert_eq!(d.handle(EventType::KeyPress(Key::Space)), None);
+        assert_eq!(d.handle(EventType::KeyRelease(Key::Space)), None);
+    }
+
+    #[test]
+    fn debounce_chord_partial_release_emits_released() {
+        let mut d = Debouncer::new(vec![Key::ControlLeft, Key::ShiftLeft, Key::Space]);
+        d.handle(EventType::KeyPress(Key::ControlLeft));
+        d.handle(EventType::KeyPress(Key::ShiftLeft));
+        assert_eq!(
+            d.handle(EventType::KeyPress(Key::Space)),
+            Some(HotkeyEvent::Pressed)
+        );
+        // Letting go of any single key ends the chord
+        assert_eq!(
+            d.handle(EventType::KeyRelease(Key::ShiftLeft)),
+            Some(HotkeyEvent::Released)
+        );
+        // Remaining releases are ignored
+        assert_eq!(d.handle(EventType::KeyRelease(Key::Space)), None);
+        assert_eq!(d.handle(EventType::KeyRelease(Key::ControlLeft)), None);
+    }
+
+    #[test]
+    fn debounce_chord_repress_after_partial_release() {
+        let mut d = Debouncer::new(vec![Key::ControlLeft, Key::Space]);
+        d.handle(EventType::KeyPress(Key::ControlLeft));
+        assert_eq!(
+            d.handle(EventType::KeyPress(Key::Space)),
+            Some(HotkeyEvent::Pressed)
+        );
+        assert_eq!(
+            d.handle(EventType::KeyRelease(Key::Space)),
+            Some(HotkeyEvent::Released)
+        );
+        // Ctrl is still held, so pressing Space again re-completes the chord
+        assert_eq!(
+            d.handle(EventType::KeyPress(Key::Space)),
+            Some(HotkeyEvent::Pressed)
+        );
+    }
+
+    const THRESHOLD: Duration = Duration::from_millis(400);
+
+    #[test]
+    fn activator_hold_follows_key() {
+        let mut a = Activator::new(Activation::Hold, THRESHOLD);
+        let t = Instant::now();
+        assert_eq!(a.handle(HotkeyEvent::Pressed, t), Some(RecordAction::Start));
+        assert_eq!(
+            a.handle(HotkeyEvent::Released, t + Duration::from_millis(50)),
+            Some(RecordAction::Stop)
+        );
+    }
+
+    #[test]
+    fn activator_toggle_taps_start_and_stop() {
+        let mut a = Activator::new(Activation::Toggle, THRESHOLD);
+        let t = Instant::now();
+        assert_eq!(a.handle(HotkeyEvent::Pressed, t), Some(RecordAction::Start));
+        assert_eq!(a.handle(HotkeyEvent::Released, t), None);
+        assert_eq!(
+            a.handle(HotkeyEvent::Pressed, t + Duration::from_secs(120)),
+            Some(RecordAction::Stop)
+        );
+        assert_eq!(a.handle(HotkeyEvent::Released, t), None);
+        assert_eq!(a.handle(HotkeyEvent::Pressed, t), Some(RecordAction::Start));
+    }
+
+    #[test]
+    fn activator_hybrid_short_tap_latches() {
+        let mut a = Activator::new(Activation::Hybrid, THRESHOLD);
+        let t = Instant::now();
+        assert_eq!(a.handle(HotkeyEvent::Pressed, t), Some(RecordAction::Start));
+        assert_eq!(
+            a.handle(HotkeyEvent::Released, t + Duration::from_millis(100)),
+            None
+        );
+        // Next tap stops, and its release is ignored
+        assert_eq!(
+            a.handle(HotkeyEvent::Pressed, t + Duration::from_secs(30)),
+            Some(RecordAction::Stop)
+        );
+        assert_eq!(
+            a.handle(HotkeyEvent::Released, t + Duration::from_secs(31)),
+            None
+        );
+    }
+
+    #[test]
+    fn activator_hybrid_long_hold_is_push_to_talk() {
+        let mut a = Activator::new(Activation::Hybrid, THRESHOLD);
+        let t = Instant::now();
+        assert_eq!(a.handle(HotkeyEvent::Pressed, t), Some(RecordAction::Start));
+        assert_eq!(
+            a.handle(HotkeyEvent::Released, t + Duration::from_secs(2)),
+            Some(RecordAction::Stop)
+        );
+        // Back to idle: the next press starts a fresh recording
+        assert_eq!(
+            a.handle(HotkeyEvent::Pressed, t + Duration::from_secs(3)),
+            Some(RecordAction::Start)
+        );
+    }
+
+    #[test]
+    fn activator_cancel_while_recording() {
+        let mut a = Activator::new(Activation::Hold, THRESHOLD);
+        let t = Instant::now();
+        assert_eq!(a.handle(HotkeyEvent::Pressed, t), Some(RecordAction::Start));
+        assert_eq!(a.handle(HotkeyEvent::Cancel, t), Some(RecordAction::Cancel));
+        // Releasing the hotkey after a cancel must not transcribe
+        assert_eq!(a.handle(HotkeyEvent::Released, t), None);
+    }
+
+    #[test]
+    fn activator_cancel_when_idle_ignored() {
+        let mut a = Activator::new(Activation::Toggle, THRESHOLD);
+        let t = Instant::now();
+        assert_eq!(a.handle(HotkeyEvent::Cancel, t), None);
+        assert_eq!(a.handle(HotkeyEvent::Pressed, t), Some(RecordAction::Start));
+    }
+
+    #[test]
+    fn activator_cancel_resets_hybrid_latch() {
+        let mut a = Activator::new(Activation::Hybrid, THRESHOLD);
+        let t = Instant::now();
+        a.handle(HotkeyEvent::Pressed, t);
+        a.handle(HotkeyEvent::Released, t + Duration::from_millis(100));
+        assert_eq!(
+            a.handle(HotkeyEvent::Cancel, t + Duration::from_secs(5)),
+            Some(RecordAction::Cancel)
+        );
+        // The next tap starts a new recording rather than stopping one
+        assert_eq!(
+            a.handle(HotkeyEvent::Pressed, t + Duration::from_secs(6)),
+            Some(RecordAction::Start)
+        );
+    }
+
+    #[test]
+    fn router_emits_cancel_and_hotkey_events() {
+        let (tx, rx) = mpsc::channel();
+        let mut router = KeyRouter::new("F9", "Escape").unwrap();
+        router.handle(EventType::KeyPress(Key::F9), &tx);
+        router.handle(EventType::KeyPress(Key::Escape), &tx);
+        router.handle(EventType::KeyPress(Key::Escape), &tx);
+        router.handle(EventType::KeyRelease(Key::F9), &tx);
+        drop(tx);
+        assert_eq!(
+            rx.iter().collect::<Vec<_>>(),
+            vec![
+                HotkeyEvent::Pressed,
+                HotkeyEvent::Cancel,
+                HotkeyEvent::Released
+            ]
+        );
+    }
+
+    #[test]
+    fn router_without_cancel_key() {
+        let (tx, rx) = mpsc::channel();
+        let mut router = KeyRouter::new("F9", "").unwrap();
+        router.handle(EventType::KeyPress(Key::Escape), &tx);
+        drop(tx);
+        assert!(rx.iter().next().is_none());
+    }
 }
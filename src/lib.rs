@@ -1,2 +1,5 @@
 pub mod config;
+mod flac;
+#[cfg(feature = "opus")]
+mod opus;
 pub mod transcribe;
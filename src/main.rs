@@ -1,26 +1,45 @@
 mod audio;
 mod config;
+mod evdev;
+mod filter;
+mod flac;
+mod format;
 mod hotkey;
+#[cfg(feature = "opus")]
+mod opus;
 mod output;
+mod ring;
 mod status;
+mod streaming;
 mod transcribe;
+mod vad;
+mod worker;
 
 use anyhow::{bail, Context, Result};
 use clap::Parser;
-use config::OutputMode;
+use config::{Activation, HotkeyBackend, OutputMode, Task};
 use std::path::Path;
 use std::sync::mpsc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use hotkey::HotkeyEvent;
-use transcribe::TranscribeBackend;
+use format::OutputFormat;
+use hotkey::{HotkeyEvent, RecordAction};
+use transcribe::{TranscribeBackend, Transcript};
+
+/// How often continuous mode pulls captured audio into the segmenter
+const CONTINUOUS_POLL: Duration = Duration::from_millis(100);
+/// How often streaming mode pulls captured audio while recording
+const STREAMING_POLL: Duration = Duration::from_millis(250);
+/// How often finished transcriptions are collected while jobs are pending
+const WORKER_POLL: Duration = Duration::from_millis(50);
 
 #[derive(Parser)]
 #[command(name = "dictr", version, about = "Push-to-talk voice dictation")]
 struct Cli {
-    /// Transcription backend: "local" or "api"
-    #[arg(long)]
-    backend: Option<String>,
+    /// Transcription backend: "local", "api", "whisper-server", "wyoming" or
+    /// "command", or a comma-separated fallback order (e.g. api,local)
+    #[arg(long, value_delimiter = ',')]
+    backend: Option<Vec<String>>,
 
     /// Path to whisper model (.bin)
     #[arg(long)]
@@ -30,6 +49,22 @@ struct Cli {
     #[arg(long)]
     hotkey: Option<String>,
 
+    /// Where to read key events from (evdev works on Wayland)
+    #[arg(long, value_enum)]
+    hotkey_backend: Option<HotkeyBackend>,
+
+    /// Keyboard for the evdev backend: /dev/input path or name substring
+    #[arg(long)]
+    evdev_device: Option<String>,
+
+    /// Key that discards the current recording (default: Escape)
+    #[arg(long)]
+    cancel_key: Option<String>,
+
+    /// How the hotkey drives recording
+    #[arg(long, value_enum)]
+    activation: Option<Activation>,
+
     /// Use clipboard paste instead of xdotool type
     #[arg(long, conflicts_with = "type_output")]
     paste: bool,
@@ -50,6 +85,14 @@ struct Cli {
     #[arg(long)]
     language: Option<String>,
 
+    /// Output English text whatever the spoken language
+    #[arg(long)]
+    translate: bool,
+
+    /// Restrict language auto-detection to these codes (e.g. en,fr,de)
+    #[arg(long, value_delimiter = ',')]
+    languages: Option<Vec<String>>,
+
     /// API endpoint URL for the api backend
     #[arg(long)]
     api_url: Option<String>,
@@ -62,10 +105,40 @@ struct Cli {
     #[arg(long)]
     min_duration: Option<u64>,
 
+    /// Transcribe recordings as captured, without trimming silence or
+    /// skipping recordings that have no speech
+    #[arg(long)]
+    no_vad: bool,
+
+    /// Keep the mic open and prepend this many ms of audio before each press
+    #[arg(long)]
+    preroll: Option<u64>,
+
+    /// Skip the warm-up inference at startup
+    #[arg(long)]
+    no_warm_up: bool,
+
+    /// Transcribe while recording and show partial text (local backend)
+    #[arg(long)]
+    streaming: bool,
+
+    /// Hands-free dictation: keep listening and transcribe at each pause
+    /// (the hotkey pauses/resumes)
+    #[arg(long, conflicts_with = "file")]
+    continuous: bool,
+
     /// Transcribe an audio file and print to stdout (skips hotkey/mic)
     #[arg(long)]
     file: Option<String>,
 
+    /// Output format for --file
+    #[arg(long, value_enum, requires = "file")]
+    format: Option<OutputFormat>,
+
+    /// Compute per-word timestamps (shown by --format vtt/json)
+    #[arg(long)]
+    word_timestamps: bool,
+
     /// Show verbose output (model loading, debug info)
     #[arg(long, short)]
     verbose: bool,
@@ -103,44 +176,38 @@ fn main() -> Result<()> {
 
     // File mode: transcribe a file and exit
     if let Some(ref file_path) = cli.file {
-        if config.backend == "api" {
-            eprintln!("warning: --file always uses local backend, ignoring --backend api");
+        if config.backend != ["local"] {
+            eprintln!("warning: --file always uses local backend, ignoring --backend");
         }
-        return transcribe_file(file_path, &config, cli.verbose);
+        let format = cli.format.unwrap_or(OutputFormat::Text);
+        return transcribe_file(file_path, format, &config, cli.verbose);
     }
 
     output::check_deps(config.output_mode)?;
 
     // Init transcription backend
-    let mut backend: Box<dyn TranscribeBackend> = match config.backend.as_str() {
-        "local" => {
-            let path = config.resolved_model_path();
-            if !path.exists() {
-                bail!(
-                    "model not found at {}. Download from https://huggingface.co/ggerganov/whisper.cpp/tree/main",
-                    path.display()
-                );
-            }
-            if cli.verbose {
-                eprintln!("loading model from {}...", path.display());
-            }
-            let path_str = path.to_str().context("invalid UTF-8 in model path")?;
-            Box::new(transcribe::LocalWhisper::new(path_str)?)
-        }
-        "api" => {
-            if config.api_key.is_empty() {
-                bail!("API key required. Set api_key in config or OPENAI_API_KEY env var");
+    let load_start = Instant::now();
+    let mut backends = Vec::new();
+    for name in &config.backend {
+        backends.push((name.clone(), build_backend(name, &config, cli.verbose)?));
+    }
+    let mut backend = transcribe::BackendChain::new(backends)?;
+    if cli.verbose {
+        eprintln!("backend ready in {} ms", load_start.elapsed().as_millis());
+    }
+    if config.warm_up {
+        let warm_start = Instant::now();
+        match backend.warm_up() {
+            Ok(()) if cli.verbose => {
+                eprintln!("warm-up took {} ms", warm_start.elapsed().as_millis());
             }
-            Box::new(transcribe::ApiWhisper::new(
-                config.api_key.clone(),
-                config.api_url.clone(),
-            )?)
+            Ok(()) => {}
+            Err(e) => eprintln!("warning: warm-up failed: {e}"),
         }
-        other => bail!("unknown backend: {other}"),
-    };
+    }
 
     // Init audio
-    let mut recorder = audio::AudioRecorder::new(config.device.as_deref())?;
+    let mut recorder = audio::AudioRecorder::new(config.device.as_deref(), config.preroll_ms)?;
     if cli.verbose {
         eprintln!(
             "mic ready: {} ({}Hz)",
@@ -151,27 +218,149 @@ fn main() -> Result<()> {
 
     // Start hotkey listener
     let (tx, rx) = mpsc::channel();
-    let _hotkey_thread = hotkey::start_listener(&config.hotkey, tx)?;
+    let _hotkey_thread = hotkey::start_listener(&config, tx)?;
+    if cli.continuous {
+        return run_continuous(&mut backend, &mut recorder, &rx, &config, cli.verbose);
+    }
+    let filter = filter::SegmentFilter::new(&config.filter, cli.verbose);
+    let streaming = config.streaming && backend.as_streaming().is_some();
+    if config.streaming && !streaming {
+        eprintln!("warning: streaming is only supported by the local backend, disabling");
+    }
     if cli.verbose {
-        eprintln!("hold [{}] to record, release to transcribe", config.hotkey);
+        let usage = match config.activation {
+            Activation::Hold => "hold to record, release to transcribe",
+            Activation::Toggle => "tap to start recording, tap again to transcribe",
+            Activation::Hybrid => "tap to latch recording, or hold for push-to-talk",
+        };
+        eprintln!("[{}] {usage}", config.hotkey);
     }
     status::set("idle");
 
+    let mut worker = worker::Worker::new(
+        backend,
+        config.language.clone(),
+        config.initial_prompt.clone(),
+        filter.clone(),
+    );
+
     // Main event loop
+    let mut activator = hotkey::Activator::new(
+        config.activation,
+        Duration::from_millis(config.hold_threshold_ms),
+    );
     let mut press_time: Option<Instant> = None;
+    // Set while a streamed recording is in progress
+    let mut streamed = false;
+    let mut recorded: Vec<f32> = Vec::new();
+    // Recording length when the last partial pass was submitted
+    let mut submitted_len = 0;
+    let step = config.streaming_step_ms as usize * 16;
 
     loop {
-        match rx.recv()? {
-            HotkeyEvent::Pressed => {
+        // Typing while the hotkey may be held down would mix in its
+        // modifiers, so results wait until the recording ends
+        if !activator.is_recording() {
+            while let Some(done) = worker.try_done() {
+                if done.cancelled {
+                    continue;
+                }
+                if cli.verbose {
+                    eprint!("({} ms) ", done.elapsed.as_millis());
+                }
+                let served = output_transcription(done.result, &config, cli.verbose)?;
+                status::set_served(idle_state(&worker), served.as_ref());
+            }
+        }
+
+        while let Some(result) = worker.try_partial() {
+            match result {
+                Ok(Some(partial)) if streamed => {
+                    status::set_with_text("recording", &partial);
+                    if cli.verbose {
+                        eprint!("\n  partial: {partial} ");
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("streaming error: {e}"),
+            }
+        }
+
+        let poll = if worker.pending() > 0 || worker.partial_pending() {
+            Some(WORKER_POLL)
+        } else if streamed {
+            Some(STREAMING_POLL)
+        } else {
+            None
+        };
+        let event = if let Some(timeout) = poll {
+            match rx.recv_timeout(timeout) {
+                Ok(event) => event,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    if !streamed {
+                        continue;
+                    }
+                    recorded.extend(recorder.drain()?);
+                    // One pass at a time, and none while an earlier
+                    // recording is transcribed
+                    let due = recorded.len() >= submitted_len + step;
+                    if due && !worker.partial_pending() && worker.pending() == 0 {
+                        submitted_len = recorded.len();
+                        worker.submit_partial(recorded.clone());
+                    }
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => bail!("hotkey listener stopped"),
+            }
+        } else {
+            rx.recv()?
+        };
+        if event == HotkeyEvent::Cancel && !activator.is_recording() && worker.pending() > 0 {
+            let cancelled = worker.cancel_all();
+            status::set("idle");
+            if cli.verbose && cancelled > 0 {
+                eprintln!("cancelled {cancelled} pending transcription(s)");
+            }
+            continue;
+        }
+        match activator.handle(event, Instant::now()) {
+            None => {}
+            Some(RecordAction::Start) => {
                 press_time = Some(Instant::now());
                 recorder.start()?;
+                if streaming {
+                    streamed = true;
+                    recorded.clear();
+                    submitted_len = 0;
+                    worker.start_streaming(streaming::StreamingSession::new(
+                        config.streaming_step_ms,
+                        filter.clone(),
+                    ));
+                }
                 status::set("recording");
                 if cli.verbose {
                     eprint!("recording... ");
                 }
             }
-            HotkeyEvent::Released => {
-                let audio = recorder.stop()?;
+            Some(RecordAction::Cancel) => {
+                press_time = None;
+                streamed = false;
+                worker.cancel_partial();
+                recorded.clear();
+                recorder.cancel();
+                status::set(idle_state(&worker));
+                if cli.verbose {
+                    eprintln!("cancelled");
+                }
+            }
+            Some(RecordAction::Stop) => {
+                let mut audio = recorder.stop()?;
+                let streamed = std::mem::take(&mut streamed);
+                worker.cancel_partial();
+                if streamed {
+                    recorded.extend(audio);
+                    audio = std::mem::take(&mut recorded);
+                }
 
                 // Skip short presses
                 let duration = press_time.take().map(|t| t.elapsed());
@@ -181,7 +370,7 @@ fn main() -> Result<()> {
                         if cli.verbose {
                             eprintln!("too short ({:.1}s), skipping", d.as_secs_f32());
                         }
-                        status::set("idle");
+                        status::set(idle_state(&worker));
                         continue;
                     }
                     if cli.verbose {
@@ -193,52 +382,274 @@ fn main() -> Result<()> {
                     if cli.verbose {
                         eprintln!("no audio captured");
                     }
-                    status::set("idle");
+                    status::set(idle_state(&worker));
                     continue;
                 }
 
-                status::set("transcribing");
-                if cli.verbose {
-                    eprint!("transcribing... ");
-                }
-                match backend.transcribe(
-                    &audio,
-                    config.language.as_deref(),
-                    config.initial_prompt.as_deref(),
-                ) {
-                    Ok(text)
-                        if text.is_empty()
-                            || text == "(BLANK AUDIO)"
-                            || text == "BLANK AUDIO"
-                            || text == "BLANK_AUDIO"
-                            || text == "(BLANK_AUDIO)" =>
-                    {
-                        if cli.verbose {
-                            eprintln!("(empty transcription)");
-                        }
-                    }
-                    Ok(text) => {
-                        let mut text = config.apply_replacements(&text);
-                        text.push(' ');
-                        if cli.verbose {
-                            eprintln!("{text}");
+                let audio = if config.vad.enabled {
+                    match vad::speech_range(&audio, &config.vad) {
+                        // Committed streaming text refers to untrimmed offsets
+                        Some(_) if streamed => audio,
+                        Some(range) => {
+                            if cli.verbose {
+                                eprint!(
+                                    "speech {:.1}s-{:.1}s ",
+                                    range.start as f32 / 16000.0,
+                                    range.end as f32 / 16000.0
+                                );
+                            }
+                            audio[range].to_vec()
                         }
-                        match config.output_mode {
-                            OutputMode::Paste => output::paste_text(&text)?,
-                            OutputMode::Type => output::type_text(&text, config.typing_delay_ms)?,
+                        None => {
+                            if cli.verbose {
+                                eprintln!("no speech detected, skipping");
+                            }
+                            status::set(idle_state(&worker));
+                            continue;
                         }
                     }
-                    Err(e) => {
-                        eprintln!("transcription error: {e}");
+                } else {
+                    audio
+                };
+
+                status::set("transcribing");
+                if cli.verbose {
+                    match worker.pending() {
+                        0 => eprintln!("transcribing..."),
+                        n => eprintln!("queued behind {n}"),
                     }
                 }
-                status::set("idle");
+                worker.submit(worker::Job { audio, streamed });
             }
         }
     }
 }
 
-fn transcribe_file(file_path: &str, config: &config::Config, verbose: bool) -> Result<()> {
+/// Status once nothing is being recorded.
+fn idle_state(worker: &worker::Worker) -> &'static str {
+    if worker.pending() > 0 {
+        "transcribing"
+    } else {
+        "idle"
+    }
+}
+
+/// Construct one entry of the `backend` list.
+fn build_backend(
+    name: &str,
+    config: &config::Config,
+    verbose: bool,
+) -> Result<Box<dyn TranscribeBackend>> {
+    if name != "local" && !config.languages.is_empty() {
+        eprintln!("warning: languages is only supported by the local backend, ignoring for {name}");
+    }
+    Ok(match name {
+        "local" if !config.models.is_empty() => {
+            let rules = config.model_rules();
+            // Models load on first use, so catch a bad path up front
+            for rule in &rules {
+                if !Path::new(&rule.path).exists() {
+                    bail!("model not found at {}", rule.path);
+                }
+            }
+            let mut router = transcribe::ModelRouter::new(rules, config.whisper.clone(), verbose);
+            router.set_allowed_languages(&config.languages)?;
+            router.set_task(config.task);
+            if config.streaming {
+                router.load_streaming_model()?;
+            }
+            Box::new(router)
+        }
+        "local" => {
+            let path = config.resolved_model_path();
+            if !path.exists() {
+                bail!(
+                    "model not found at {}. Download from https://huggingface.co/ggerganov/whisper.cpp/tree/main",
+                    path.display()
+                );
+            }
+            if verbose {
+                eprintln!("loading model from {}...", path.display());
+            }
+            let path_str = path.to_str().context("invalid UTF-8 in model path")?;
+            let mut local = transcribe::LocalWhisper::new(path_str, config.whisper.clone())?;
+            local.set_allowed_languages(&config.languages)?;
+            local.set_task(config.task);
+            Box::new(local)
+        }
+        "api" => {
+            if config.api_key.is_empty() {
+                bail!("API key required. Set api_key in config or OPENAI_API_KEY env var");
+            }
+            let mut api = transcribe::ApiWhisper::new(
+                config.api_key.clone(),
+                config.api_url.clone(),
+                config.api.clone(),
+            )?;
+            api.set_task(config.task)?;
+            Box::new(api)
+        }
+        "whisper-server" => {
+            let mut server = transcribe::WhisperServer::new(config.whisper_server.clone())?;
+            server.set_task(config.task);
+            Box::new(server)
+        }
+        "wyoming" => {
+            let mut wyoming = transcribe::Wyoming::new(config.wyoming.clone());
+            wyoming.set_task(config.task)?;
+            Box::new(wyoming)
+        }
+        "command" => {
+            let mut command = transcribe::ExternalCommand::new(
+                config.command.clone(),
+                config.command_timeout_ms,
+            )?;
+            command.set_task(config.task);
+            command.set_ignore_stderr(config.command_ignore_stderr);
+            Box::new(command)
+        }
+        other => bail!("unknown backend: {other}"),
+    })
+}
+
+/// Transcribe and drop the segments rejected by the hallucination filter.
+fn transcribe_filtered(
+    backend: &mut dyn TranscribeBackend,
+    audio: &[f32],
+    config: &config::Config,
+    filter: &filter::SegmentFilter,
+) -> Result<Transcript> {
+    let mut transcript = backend.transcribe(
+        audio,
+        config.language.as_deref(),
+        config.initial_prompt.as_deref(),
+    )?;
+    transcript.segments = filter.apply(transcript.segments);
+    Ok(transcript)
+}
+
+/// Apply replacements and type/paste a transcription. Errors from the backend
+/// are reported but not fatal; output failures are. Returns the transcript so
+/// the caller can report its language and backend.
+fn output_transcription(
+    result: Result<Transcript>,
+    config: &config::Config,
+    verbose: bool,
+) -> Result<Option<Transcript>> {
+    let transcript = match result {
+        Ok(transcript) => transcript,
+        Err(e) => {
+            eprintln!("transcription error: {e}");
+            return Ok(None);
+        }
+    };
+    if verbose {
+        if let Some(backend) = &transcript.backend {
+            eprint!("(via {backend}) ");
+        }
+        if let Some(language) = &transcript.language {
+            eprint!("[{language}] ");
+        }
+    }
+    // Whisper's marker for silence. The VAD keeps silent recordings from
+    // reaching the model; this catches the ones it lets through, those of
+    // `--no-vad`, and backends that answer with the marker themselves.
+    match transcript.text() {
+        text if text.is_empty()
+            || text == "(BLANK AUDIO)"
+            || text == "BLANK AUDIO"
+            || text == "BLANK_AUDIO"
+            || text == "(BLANK_AUDIO)" =>
+        {
+            if verbose {
+                eprintln!("(empty transcription)");
+            }
+        }
+        text => {
+            let mut text = config.apply_replacements(&text);
+            text.push(' ');
+            if verbose {
+                eprintln!("{text}");
+            }
+            match config.output_mode {
+                OutputMode::Paste => output::paste_text(&text)?,
+                OutputMode::Type => output::type_text(&text, config.typing_delay_ms)?,
+            }
+        }
+    }
+    Ok(Some(transcript))
+}
+
+/// Hands-free mode: the mic stays open and every utterance that ends in a
+/// pause is transcribed and output. The hotkey pauses and resumes listening.
+fn run_continuous(
+    backend: &mut dyn TranscribeBackend,
+    recorder: &mut audio::AudioRecorder,
+    rx: &mpsc::Receiver<HotkeyEvent>,
+    config: &config::Config,
+    verbose: bool,
+) -> Result<()> {
+    let mut segmenter = vad::Segmenter::new(&config.vad);
+    let filter = filter::SegmentFilter::new(&config.filter, verbose);
+    let mut listening = true;
+    recorder.start()?;
+    status::set("listening");
+    if verbose {
+        eprintln!("listening, tap [{}] to pause/resume", config.hotkey);
+    }
+
+    loop {
+        match rx.recv_timeout(CONTINUOUS_POLL) {
+            Ok(HotkeyEvent::Pressed) => {
+                listening = !listening;
+                segmenter.reset();
+                if listening {
+                    recorder.start()?;
+                    status::set("listening");
+                } else {
+                    recorder.cancel();
+                    status::set("paused");
+                }
+                if verbose {
+                    eprintln!("{}", if listening { "resumed" } else { "paused" });
+                }
+            }
+            Ok(HotkeyEvent::Cancel) if listening => {
+                recorder.drain()?;
+                segmenter.reset();
+                if verbose {
+                    eprintln!("cancelled");
+                }
+            }
+            Ok(_) | Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => bail!("hotkey listener stopped"),
+        }
+        if !listening {
+            continue;
+        }
+
+        for utterance in segmenter.push(&recorder.drain()?) {
+            status::set("transcribing");
+            if verbose {
+                eprint!("{:.1}s transcribing... ", utterance.len() as f32 / 16000.0);
+            }
+            let start = Instant::now();
+            let result = transcribe_filtered(backend, &utterance, config, &filter);
+            if verbose {
+                eprint!("({} ms) ", start.elapsed().as_millis());
+            }
+            let served = output_transcription(result, config, verbose)?;
+            status::set_served("listening", served.as_ref());
+        }
+    }
+}
+
+fn transcribe_file(
+    file_path: &str,
+    format: OutputFormat,
+    config: &config::Config,
+    verbose: bool,
+) -> Result<()> {
     let input = Path::new(file_path);
     if !input.exists() {
         bail!("file not found: {}", input.display());
@@ -249,15 +660,11 @@ fn transcribe_file(file_path: &str, config: &config::Config, verbose: bool) -> R
         bail!("no audio samples decoded from {}", input.display());
     }
 
-    let model_path = config.resolved_model_path();
-    if !model_path.exists() {
-        bail!("model not found at {}", model_path.display());
-    }
+    let load_start = Instant::now();
+    let mut backend = build_backend("local", config, verbose)?;
     if verbose {
-        eprintln!("loading model from {}...", model_path.display());
+        eprintln!("backend ready in {} ms", load_start.elapsed().as_millis());
     }
-    let path_str = model_path.to_str().context("invalid UTF-8 in model path")?;
-    let mut backend = transcribe::LocalWhisper::new(path_str)?;
 
     if verbose {
         eprintln!(
@@ -268,14 +675,25 @@ fn transcribe_file(file_path: &str, config: &config::Config, verbose: bool) -> R
         );
     }
 
-    let text = backend.transcribe(
-        &audio,
-        config.language.as_deref(),
-        config.initial_prompt.as_deref(),
-    )?;
+    let start = Instant::now();
+    let filter = filter::SegmentFilter::new(&config.filter, verbose);
+    let mut transcript = transcribe_filtered(backend.as_mut(), &audio, config, &filter)?;
+    if verbose {
+        eprintln!("transcribed in {} ms", start.elapsed().as_millis());
+        if let Some(language) = &transcript.language {
+            eprintln!("language: {language}");
+        }
+    }
 
-    let text = config.apply_replacements(&text);
-    println!("{text}");
+    if format == OutputFormat::Text {
+        // Replace across segment boundaries, as in dictation
+        println!("{}", config.apply_replacements(&transcript.text()));
+        return Ok(());
+    }
+    for segment in &mut transcript.segments {
+        segment.text = config.apply_replacements(&segment.text);
+    }
+    print!("{}", format::render(format, &transcript)?);
     Ok(())
 }
 
@@ -327,11 +745,25 @@ fn apply_cli_overrides(config: &mut config::Config, cli: &Cli) {
         config.backend = b.clone();
     }
     if let Some(m) = &cli.model {
+        // An explicit model replaces the duration rules
         config.model_path = m.clone();
+        config.models.clear();
     }
     if let Some(h) = &cli.hotkey {
         config.hotkey = h.clone();
     }
+    if let Some(b) = cli.hotkey_backend {
+        config.hotkey_backend = b;
+    }
+    if cli.evdev_device.is_some() {
+        config.evdev_device = cli.evdev_device.clone();
+    }
+    if let Some(k) = &cli.cancel_key {
+        config.cancel_key = k.clone();
+    }
+    if let Some(a) = cli.activation {
+        config.activation = a;
+    }
     if cli.device.is_some() {
         config.device = cli.device.clone();
     }
@@ -347,6 +779,27 @@ fn apply_cli_overrides(config: &mut config::Config, cli: &Cli) {
     if let Some(ms) = cli.min_duration {
         config.min_duration_ms = ms;
     }
+    if cli.translate {
+        config.task = Task::Translate;
+    }
+    if let Some(ref languages) = cli.languages {
+        config.languages = languages.clone();
+    }
+    if cli.word_timestamps {
+        config.whisper.word_timestamps = true;
+    }
+    if cli.no_warm_up {
+        config.warm_up = false;
+    }
+    if cli.streaming {
+        config.streaming = true;
+    }
+    if cli.no_vad {
+        config.vad.enabled = false;
+    }
+    if let Some(ms) = cli.preroll {
+        config.preroll_ms = ms;
+    }
     if cli.paste {
         config.output_mode = OutputMode::Paste;
     }
@@ -371,15 +824,45 @@ mod tests {
         let mut config = config::Config::default();
         let cli = parse_args(&["--backend", "api"]);
         apply_cli_overrides(&mut config, &cli);
-        assert_eq!(config.backend, "api");
+        assert_eq!(config.backend, ["api"]);
+
+        let cli = parse_args(&["--backend", "api,local"]);
+        apply_cli_overrides(&mut config, &cli);
+        assert_eq!(config.backend, ["api", "local"]);
     }
 
     #[test]
     fn cli_override_model() {
         let mut config = config::Config::default();
+        config.models.push(config::ModelRule {
+            max_secs: Some(5.0),
+            path: "/tmp/base.bin".into(),
+        });
         let cli = parse_args(&["--model", "/tmp/model.bin"]);
         apply_cli_overrides(&mut config, &cli);
         assert_eq!(config.model_path, "/tmp/model.bin");
+        assert!(config.models.is_empty());
+    }
+
+    #[test]
+    fn model_rules_with_catch_all_skip_model_path() {
+        let dir = std::env::temp_dir().join(format!("dictr-rules-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut config = config::Config {
+            model_path: dir.join("missing.bin").to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        for (max_secs, name) in [(Some(5.0), "base.bin"), (None, "medium.bin")] {
+            let path = dir.join(name);
+            std::fs::write(&path, b"").unwrap();
+            config.models.push(config::ModelRule {
+                max_secs,
+                path: path.to_string_lossy().into_owned(),
+            });
+        }
+        let result = build_backend("local", &config, false);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(result.is_ok(), "{:?}", result.err());
     }
 
     #[test]
@@ -390,6 +873,36 @@ mod tests {
         assert_eq!(config.hotkey, "F9");
     }
 
+    #[test]
+    fn cli_override_hotkey_backend() {
+        let mut config = config::Config::default();
+        let cli = parse_args(&[
+            "--hotkey-backend",
+            "evdev",
+            "--evdev-device",
+            "/dev/input/event3",
+        ]);
+        apply_cli_overrides(&mut config, &cli);
+        assert_eq!(config.hotkey_backend, HotkeyBackend::Evdev);
+        assert_eq!(config.evdev_device, Some("/dev/input/event3".into()));
+    }
+
+    #[test]
+    fn cli_override_cancel_key() {
+        let mut config = config::Config::default();
+        let cli = parse_args(&["--cancel-key", "F10"]);
+        apply_cli_overrides(&mut config, &cli);
+        assert_eq!(config.cancel_key, "F10");
+    }
+
+    #[test]
+    fn cli_override_activation() {
+        let mut config = config::Config::default();
+        let cli = parse_args(&["--activation", "hybrid"]);
+        apply_cli_overrides(&mut config, &cli);
+        assert_eq!(config.activation, Activation::Hybrid);
+    }
+
     #[test]
     fn cli_override_device() {
         let mut config = config::Config::default();
@@ -406,6 +919,22 @@ mod tests {
         assert_eq!(config.language, Some("fr".into()));
     }
 
+    #[test]
+    fn cli_override_languages() {
+        let mut config = config::Config::default();
+        let cli = parse_args(&["--languages", "en,fr,de"]);
+        apply_cli_overrides(&mut config, &cli);
+        assert_eq!(config.languages, vec!["en", "fr", "de"]);
+    }
+
+    #[test]
+    fn cli_override_translate() {
+        let mut config = config::Config::default();
+        let cli = parse_args(&["--translate"]);
+        apply_cli_overrides(&mut config, &cli);
+        assert_eq!(config.task, Task::Translate);
+    }
+
     #[test]
     fn cli_override_api_url() {
         let mut config = config::Config::default();
@@ -430,6 +959,53 @@ mod tests {
         assert_eq!(config.min_duration_ms, 500);
     }
 
+    #[test]
+    fn cli_override_word_timestamps() {
+        let mut config = config::Config::default();
+        let cli = parse_args(&["--word-timestamps"]);
+        apply_cli_overrides(&mut config, &cli);
+        assert!(config.whisper.word_timestamps);
+    }
+
+    #[test]
+    fn cli_format_requires_file() {
+        assert!(Cli::try_parse_from(["dictr", "--format", "srt"]).is_err());
+        let cli = parse_args(&["--file", "talk.mp3", "--format", "srt"]);
+        assert_eq!(cli.format, Some(OutputFormat::Srt));
+    }
+
+    #[test]
+    fn cli_override_no_warm_up() {
+        let mut config = config::Config::default();
+        let cli = parse_args(&["--no-warm-up"]);
+        apply_cli_overrides(&mut config, &cli);
+        assert!(!config.warm_up);
+    }
+
+    #[test]
+    fn cli_override_streaming() {
+        let mut config = config::Config::default();
+        let cli = parse_args(&["--streaming"]);
+        apply_cli_overrides(&mut config, &cli);
+        assert!(config.streaming);
+    }
+
+    #[test]
+    fn cli_override_no_vad() {
+        let mut config = config::Config::default();
+        let cli = parse_args(&["--no-vad"]);
+        apply_cli_overrides(&mut config, &cli);
+        assert!(!config.vad.enabled);
+    }
+
+    #[test]
+    fn cli_override_preroll() {
+        let mut config = config::Config::default();
+        let cli = parse_args(&["--preroll", "200"]);
+        apply_cli_overrides(&mut config, &cli);
+        assert_eq!(config.preroll_ms, 200);
+    }
+
     #[test]
     fn cli_override_paste_output_mode() {
         let mut config = config::Config {
@@ -461,6 +1037,13 @@ mod tests {
         assert_eq!(cli.file, Some("/tmp/voice.ogg".into()));
     }
 
+    #[test]
+    fn cli_continuous_flag() {
+        assert!(parse_args(&["--continuous"]).continuous);
+        assert!(!parse_args(&[]).continuous);
+        assert!(Cli::try_parse_from(["dictr", "--continuous", "--file", "a.ogg"]).is_err());
+    }
+
     #[test]
     fn cli_file_with_language_and_prompt() {
         let cli = parse_args(&[
@@ -481,8 +1064,9 @@ mod tests {
         let mut config = config::Config::default();
         let cli = parse_args(&[]);
         apply_cli_overrides(&mut config, &cli);
-        assert_eq!(config.backend, "local");
+        assert_eq!(config.backend, ["local"]);
         assert_eq!(config.hotkey, "AltGr");
+        assert_eq!(config.activation, Activation::Hold);
         assert_eq!(config.output_mode, OutputMode::Paste);
         assert_eq!(config.min_duration_ms, 300);
         assert!(config.device.is_none());
@@ -0,0 +1,170 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Fixed-size single-producer single-consumer queue of samples. The producer
+/// side never locks or allocates, so it is safe to use from the realtime
+/// audio callback.
+struct Ring {
+    buf: Box<[UnsafeCell<f32>]>,
+    mask: usize,
+    /// Total samples written; only the producer stores it
+    head: AtomicUsize,
+    /// Total samples read; only the consumer stores it
+    tail: AtomicUsize,
+    /// Samples lost because the consumer fell behind
+    dropped: AtomicUsize,
+}
+
+// SAFETY: slots between tail and head belong to the consumer, the others to
+// the producer; the Release/Acquire pairs on head and tail hand them over.
+unsafe impl Sync for Ring {}
+
+pub struct Producer {
+    ring: Arc<Ring>,
+}
+
+pub struct Consumer {
+    ring: Arc<Ring>,
+}
+
+/// Create a queue holding at least `min_capacity` samples.
+pub fn channel(min_capacity: usize) -> (Producer, Consumer) {
+    let capacity = min_capacity.max(2).next_power_of_two();
+    let buf = (0..capacity).map(|_| UnsafeCell::new(0.0)).collect();
+    let ring = Arc::new(Ring {
+        buf,
+        mask: capacity - 1,
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        dropped: AtomicUsize::new(0),
+    });
+    (
+        Producer {
+            ring: Arc::clone(&ring),
+        },
+        Consumer { ring },
+    )
+}
+
+impl Producer {
+    /// Queue one sample. When the queue is full the sample is dropped and
+    /// counted instead of blocking.
+    pub fn push(&mut self, sample: f32) -> bool {
+        let ring = &*self.ring;
+        let head = ring.head.load(Ordering::Relaxed);
+        let tail = ring.tail.load(Ordering::Acquire);
+        if head.wrapping_sub(tail) > ring.mask {
+            ring.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        // SAFETY: the slot at head is not readable by the consumer until the
+        // store below publishes it.
+        unsafe { *ring.buf[head & ring.mask].get() = sample };
+        ring.head.store(head.wrapping_add(1), Ordering::Release);
+        true
+    }
+}
+
+impl Consumer {
+    /// Hand every queued sample to `f` in order; returns how many there were.
+    pub fn pop_each(&mut self, mut f: impl FnMut(f32)) -> usize {
+        let ring = &*self.ring;
+        let tail = ring.tail.load(Ordering::Relaxed);
+        let head = ring.head.load(Ordering::Acquire);
+        let available = head.wrapping_sub(tail);
+        for i in 0..available {
+            // SAFETY: slots in tail..head were published by the producer and
+            // are not reused until tail moves past them.
+            f(unsafe { *ring.buf[tail.wrapping_add(i) & ring.mask].get() });
+        }
+        ring.tail
+            .store(tail.wrapping_add(available), Ordering::Release);
+        available
+    }
+
+    /// Number of samples dropped since the last call.
+    pub fn take_dropped(&self) -> usize {
+        self.ring.dropped.swap(0, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pop_all(c: &mut Consumer) -> Vec<f32> {
+        let mut out = Vec::new();
+        c.pop_each(|s| out.push(s));
+        out
+    }
+
+    #[test]
+    fn capacity_rounds_up_to_power_of_two() {
+        let (mut p, mut c) = channel(3);
+        for i in 0..4 {
+            assert!(p.push(i as f32));
+        }
+        assert!(!p.push(4.0));
+        assert_eq!(pop_all(&mut c), vec![0.0, 1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn fifo_order_across_wraparound() {
+        let (mut p, mut c) = channel(4);
+        let mut expected = Vec::new();
+        let mut got = Vec::new();
+        for i in 0..10 {
+            p.push(i as f32);
+            p.push(i as f32 + 0.5);
+            expected.extend([i as f32, i as f32 + 0.5]);
+            got.extend(pop_all(&mut c));
+        }
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn full_queue_drops_and_counts() {
+        let (mut p, mut c) = channel(2);
+        assert!(p.push(1.0));
+        assert!(p.push(2.0));
+        assert!(!p.push(3.0));
+        assert!(!p.push(4.0));
+        assert_eq!(c.take_dropped(), 2);
+        assert_eq!(c.take_dropped(), 0);
+        assert_eq!(pop_all(&mut c), vec![1.0, 2.0]);
+        // Space is reclaimed after popping
+        assert!(p.push(5.0));
+    }
+
+    #[test]
+    fn empty_pop_returns_zero() {
+        let (_p, mut c) = channel(8);
+        assert_eq!(c.pop_each(|_| panic!("queue should be empty")), 0);
+    }
+
+    #[test]
+    fn concurrent_producer_consumer_keeps_order() {
+        const N: usize = 200_000;
+        let (mut p, mut c) = channel(1024);
+        let producer = std::thread::spawn(move || {
+            let mut i = 0;
+            while i < N {
+                if p.push(i as f32) {
+                    i += 1;
+                } else {
+                    std::thread::yield_now();
+                }
+            }
+        });
+
+        let mut next = 0usize;
+        while next < N {
+            c.pop_each(|s| {
+                assert_eq!(s, next as f32);
+                next += 1;
+            });
+        }
+        producer.join().unwrap();
+    }
+}
@@ -1,6 +1,8 @@
 use std::process::Command;
 use std::sync::atomic::{AtomicBool, Ordering};
 
+use crate::transcribe::Transcript;
+
 const I3BLOCKS_SIGNAL: u8 = 11;
 const MAX_PATH_LEN: usize = 128;
 
@@ -14,6 +16,32 @@ fn status_path() -> String {
 }
 
 pub fn set(state: &str) {
+    write(state);
+}
+
+/// Set the state with extra text (e.g. partial transcription) on the
+/// following line. Readers that only care about the state use the first line.
+pub fn set_with_text(state: &str, text: &str) {
+    write(&format!("{state}\n{text}"));
+}
+
+/// Set the state with what served the last transcription on the following
+/// line: its language and, after a fallback chain, the backend ("fr via api").
+pub fn set_served(state: &str, transcript: Option<&Transcript>) {
+    let Some(transcript) = transcript else {
+        return set(state);
+    };
+    match (&transcript.language, &transcript.backend) {
+        (Some(language), Some(backend)) => {
+            set_with_text(state, &format!("{language} via {backend}"))
+        }
+        (Some(language), None) => set_with_text(state, language),
+        (None, Some(backend)) => set_with_text(state, &format!("via {backend}")),
+        (None, None) => set(state),
+    }
+}
+
+fn write(contents: &str) {
     if !REGISTERED.swap(true, Ordering::Relaxed) {
         let path = status_path();
         let bytes = path.as_bytes();
@@ -27,7 +55,7 @@ pub fn set(state: &str) {
         }
         register_cleanup();
     }
-    let _ = std::fs::write(status_path(), state);
+    let _ = std::fs::write(status_path(), contents);
     signal_i3blocks();
 }
 
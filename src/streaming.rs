@@ -0,0 +1,290 @@
+use anyhow::{Context, Result};
+
+use crate::filter::SegmentFilter;
+use crate::transcribe::{join_segments, Segment, TranscribeBackend, Transcript};
+
+const SAMPLES_PER_MS: usize = 16;
+/// Once the uncommitted window is this long, all but its last segment are
+/// committed so later passes only re-transcribe the tail
+const COMMIT_AFTER_MS: usize = 10_000;
+
+/// Transcribes a recording in growing windows while it is captured. Segments
+/// that whisper has settled on are committed; on release only the audio after
+/// the last committed segment is transcribed again.
+pub struct StreamingSession {
+    step: usize,
+    filter: SegmentFilter,
+    /// Settled segments, timed relative to the start of the recording
+    committed: Vec<Segment>,
+    /// Samples covered by `committed`
+    committed_len: usize,
+    /// Recording length at the last inference pass
+    last_len: usize,
+}
+
+impl StreamingSession {
+    pub fn new(step_ms: u64, filter: SegmentFilter) -> Self {
+        Self {
+            step: step_ms as usize * SAMPLES_PER_MS,
+            filter,
+            committed: Vec::new(),
+            committed_len: 0,
+            last_len: 0,
+        }
+    }
+
+    /// Re-transcribe the uncommitted window once `step_ms` of new audio has
+    /// arrived. `audio` is the whole recording so far. Returns the partial
+    /// text when it was updated.
+    pub fn update(
+        &mut self,
+        backend: &mut dyn TranscribeBackend,
+        audio: &[f32],
+        language: Option<&str>,
+        initial_prompt: Option<&str>,
+    ) -> Result<Option<String>> {
+        if audio.len() < self.last_len + self.step {
+            return Ok(None);
+        }
+        self.last_len = audio.len();
+        let backend = backend
+            .as_streaming()
+            .context("backend does not support streaming")?;
+
+        let window = &audio[self.committed_len..];
+        let mut segments = backend
+            .transcribe_segments(window, language, initial_prompt)?
+            .segments;
+        if window.len() >= COMMIT_AFTER_MS * SAMPLES_PER_MS && segments.len() >= 2 {
+            // The last segment may still change as more audio arrives
+            let rest = segments.split_off(segments.len() - 1);
+            let end = segments[segments.len() - 1].end_ms as usize * SAMPLES_PER_MS;
+            let stable = self.filter.apply(segments);
+            self.commit(stable, end.min(window.len()));
+            segments = rest;
+        }
+        segments.retain(|s| self.filter.keep(s));
+        let mut all = self.committed.clone();
+        all.extend(segments);
+        Ok(Some(join_segments(&all)))
+    }
+
+    /// Transcribe what is left after the committed segments and return the
+    /// full transcription of `audio`. The backend picks its model by the
+    /// length of the whole recording; when that is not the streaming model,
+    /// the committed segments are dropped and everything is transcribed again.
+    pub fn finish(
+        &mut self,
+        backend: &mut dyn TranscribeBackend,
+        audio: &[f32],
+        language: Option<&str>,
+        initial_prompt: Option<&str>,
+    ) -> Result<Transcript> {
+        let keep = backend
+            .as_streaming()
+            .is_none_or(|backend| backend.keeps_partials(audio.len()));
+        if !keep {
+            self.committed.clear();
+            self.committed_len = 0;
+        }
+        let tail = &audio[self.committed_len.min(audio.len())..];
+        let transcript = backend.transcribe(tail, language, initial_prompt)?;
+        let offset_ms = self.offset_ms();
+        let mut segments = std::mem::take(&mut self.committed);
+        segments.extend(
+            self.filter
+                .apply(transcript.segments)
+                .into_iter()
+                .map(|s| shifted(s, offset_ms)),
+        );
+        Ok(Transcript {
+            segments,
+            ..transcript
+        })
+    }
+
+    fn commit(&mut self, stable: Vec<Segment>, samples: usize) {
+        let offset_ms = self.offset_ms();
+        self.committed
+            .extend(stable.into_iter().map(|s| shifted(s, offset_ms)));
+        self.committed_len += samples;
+    }
+
+    fn offset_ms(&self) -> u64 {
+        (self.committed_len / SAMPLES_PER_MS) as u64
+    }
+}
+
+/// Move a segment from window time to recording time.
+fn shifted(mut segment: Segment, offset_ms: u64) -> Segment {
+    segment.start_ms += offset_ms;
+    segment.end_ms += offset_ms;
+    for word in &mut segment.words {
+        word.start_ms += offset_ms;
+        word.end_ms += offset_ms;
+    }
+    segment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FilterConfig;
+    use crate::transcribe::StreamingTranscribe;
+
+    /// Pretends every full second of audio is one spoken word "wN", where N
+    /// is the absolute second, and records the window lengths it was given.
+    /// Recordings longer than `max_streamed` samples would go to another
+    /// model.
+    struct FakeBackend {
+        calls: Vec<usize>,
+        max_streamed: usize,
+    }
+
+    impl TranscribeBackend for FakeBackend {
+        fn transcribe(
+            &mut self,
+            audio: &[f32],
+            language: Option<&str>,
+            initial_prompt: Option<&str>,
+        ) -> Result<Transcript> {
+            self.transcribe_segments(audio, language, initial_prompt)
+        }
+
+        fn as_streaming(&mut self) -> Option<&mut dyn StreamingTranscribe> {
+            Some(self)
+        }
+    }
+
+    impl StreamingTranscribe for FakeBackend {
+        fn transcribe_segments(
+            &mut self,
+            audio: &[f32],
+            language: Option<&str>,
+            _initial_prompt: Option<&str>,
+        ) -> Result<Transcript> {
+            self.calls.push(audio.len());
+            // Absolute start is encoded in the first sample
+            let first = audio.first().map(|s| *s as usize).unwrap_or(0);
+            let segments = (0..audio.len() / 16_000)
+                .map(|i| Segment {
+                    text: format!("w{}", first + i),
+                    start_ms: i as u64 * 1000,
+                    end_ms: (i as u64 + 1) * 1000,
+                    avg_logprob: None,
+                    no_speech_prob: None,
+                    words: Vec::new(),
+                })
+                .collect();
+            Ok(Transcript {
+                language: language.map(String::from),
+                segments,
+                ..Default::default()
+            })
+        }
+
+        fn keeps_partials(&mut self, samples: usize) -> bool {
+            samples <= self.max_streamed
+        }
+    }
+
+    /// `secs` seconds of audio where each sample holds its absolute second
+    fn recording(secs: usize) -> Vec<f32> {
+        (0..secs * 16_000).map(|i| (i / 16_000) as f32).collect()
+    }
+
+    fn session() -> StreamingSession {
+        StreamingSession::new(2000, SegmentFilter::new(&FilterConfig::default(), false))
+    }
+
+    fn backend() -> FakeBackend {
+        FakeBackend {
+            calls: Vec::new(),
+            max_streamed: usize::MAX,
+        }
+    }
+
+    #[test]
+    fn waits_for_a_full_step() {
+        let mut b = backend();
+        let mut s = session();
+        assert_eq!(s.update(&mut b, &recording(1), None, None).unwrap(), None);
+        assert_eq!(
+            s.update(&mut b, &recording(2), None, None).unwrap(),
+            Some("w0 w1".into())
+        );
+        // No new audio since the last pass
+        assert_eq!(s.update(&mut b, &recording(2), None, None).unwrap(), None);
+        assert_eq!(b.calls.len(), 1);
+    }
+
+    #[test]
+    fn short_recording_finishes_with_full_pass() {
+        let mut b = backend();
+        let mut s = session();
+        s.update(&mut b, &recording(4), None, None).unwrap();
+        let transcript = s.finish(&mut b, &recording(5), None, None).unwrap();
+        assert_eq!(transcript.text(), "w0 w1 w2 w3 w4");
+        // Nothing committed yet, so the final pass sees the whole recording
+        assert_eq!(*b.calls.last().unwrap(), 5 * 16_000);
+    }
+
+    #[test]
+    fn long_recording_commits_and_finishes_only_the_tail() {
+        let mut b = backend();
+        let mut s = session();
+        let partial = s.update(&mut b, &recording(12), None, None).unwrap();
+        assert_eq!(
+            partial,
+            Some("w0 w1 w2 w3 w4 w5 w6 w7 w8 w9 w10 w11".into())
+        );
+        // Everything but the last segment (w11) is committed
+        assert_eq!(s.committed_len, 11 * 16_000);
+
+        let partial = s.update(&mut b, &recording(14), None, None).unwrap();
+        assert_eq!(
+            partial,
+            Some("w0 w1 w2 w3 w4 w5 w6 w7 w8 w9 w10 w11 w12 w13".into())
+        );
+        assert_eq!(b.calls[1], 3 * 16_000);
+
+        let transcript = s.finish(&mut b, &recording(15), Some("de"), None).unwrap();
+        assert_eq!(
+            transcript.text(),
+            "w0 w1 w2 w3 w4 w5 w6 w7 w8 w9 w10 w11 w12 w13 w14"
+        );
+        assert_eq!(*b.calls.last().unwrap(), 4 * 16_000);
+        // Segments from the tail pass are timed from the start of the recording
+        let last = transcript.segments.last().unwrap();
+        assert_eq!((last.start_ms, last.end_ms), (14_000, 15_000));
+        assert_eq!(transcript.segments[10].start_ms, 10_000);
+        assert_eq!(transcript.language.as_deref(), Some("de"));
+    }
+
+    #[test]
+    fn finish_without_updates_transcribes_everything() {
+        let mut b = backend();
+        let mut s = session();
+        assert_eq!(
+            s.finish(&mut b, &recording(3), None, None).unwrap().text(),
+            "w0 w1 w2"
+        );
+    }
+
+    #[test]
+    fn model_switch_retranscribes_the_whole_recording() {
+        let mut b = backend();
+        b.max_streamed = 14 * 16_000;
+        let mut s = session();
+        s.update(&mut b, &recording(12), None, None).unwrap();
+        assert_eq!(s.committed_len, 11 * 16_000);
+
+        let transcript = s.finish(&mut b, &recording(15), None, None).unwrap();
+        assert_eq!(*b.calls.last().unwrap(), 15 * 16_000);
+        assert_eq!(
+            transcript.text(),
+            "w0 w1 w2 w3 w4 w5 w6 w7 w8 w9 w10 w11 w12 w13 w14"
+        );
+        assert_eq!(transcript.segments.len(), 15);
+    }
+}
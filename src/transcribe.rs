@@ -1,138 +1,1708 @@
-use anyhow::{Context, Result};
-use serde::Deserialize;
-use std::io::Cursor;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
-pub trait TranscribeBackend {
+use crate::config::{
+    ApiConfig, AudioFormat, ModelRule, ResponseFormat, Strategy, Task, WhisperConfig,
+    WhisperServerConfig, WyomingConfig,
+};
+use crate::flac;
+#[cfg(feature = "opus")]
+use crate::opus;
+
+pub trait TranscribeBackend: Send {
     fn transcribe(
         &mut self,
         audio_f32_16khz: &[f32],
         language: Option<&str>,
         initial_prompt: Option<&str>,
-    ) -> Result<String>;
+    ) -> Result<Transcript>;
+
+    /// Run a throwaway inference so the first real one is not slowed down by
+    /// lazy allocations. A no-op for backends without local state.
+    fn warm_up(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Backends that can transcribe while recording return themselves here.
+    fn as_streaming(&mut self) -> Option<&mut dyn StreamingTranscribe> {
+        None
+    }
+
+    /// Watch `token` during later calls and give up with an error as soon as
+    /// it is cancelled. Backends that cannot be interrupted ignore it.
+    fn set_cancel_token(&mut self, _token: CancelToken) {}
+}
+
+/// Extension for backends that can transcribe a recording incrementally:
+/// segment timestamps let the caller commit the stable start of a growing
+/// window and only re-transcribe the rest.
+pub trait StreamingTranscribe {
+    fn transcribe_segments(
+        &mut self,
+        audio_f32_16khz: &[f32],
+        language: Option<&str>,
+        initial_prompt: Option<&str>,
+    ) -> Result<Transcript>;
+
+    /// Whether the final pass over a recording of `samples` uses the same
+    /// model as the partial passes, so their committed segments can be kept.
+    /// When not, the whole recording is transcribed again.
+    fn keeps_partials(&mut self, _samples: usize) -> bool {
+        true
+    }
+}
+
+/// Shared flag another thread sets to abort the transcription in progress.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The result of transcribing one clip.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Transcript {
+    /// Language the audio was transcribed as, when the backend reports it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Backend that served the request, when a fallback chain was tried
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backend: Option<String>,
+    pub segments: Vec<Segment>,
+}
+
+impl Transcript {
+    pub fn text(&self) -> String {
+        join_segments(&self.segments)
+    }
+}
+
+/// A piece of transcribed text with its position in the audio.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Segment {
+    pub text: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    /// Mean log probability of the text tokens, when the backend reports it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avg_logprob: Option<f32>,
+    /// Probability that the audio of the segment holds no speech, when the
+    /// backend reports it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_speech_prob: Option<f32>,
+    /// Per-word timing, when word timestamps are enabled
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<Word>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Word {
+    pub text: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+// --- Local whisper-rs backend ---
+
+/// Length of the silent buffer used by `warm_up`
+const WARM_UP_SAMPLES: usize = 16_000;
+/// Audio whisper decodes at once (30 s)
+const WINDOW_SAMPLES: usize = 30 * 16_000;
+
+pub struct LocalWhisper {
+    ctx: whisper_rs::WhisperContext,
+    /// Reused across calls so the KV caches and compute buffers are only
+    /// allocated once.
+    state: whisper_rs::WhisperState,
+    params: WhisperConfig,
+    /// Token ids from here on are special (timestamps, language, etc.)
+    token_eot: whisper_rs::WhisperToken,
+    /// Whisper language ids auto-detection is restricted to; empty means
+    /// no detection when no language is given
+    allowed_languages: Vec<i32>,
+    task: Task,
+    cancel: CancelToken,
+}
+
+impl LocalWhisper {
+    pub fn new(model_path: &str, params: WhisperConfig) -> Result<Self> {
+        validate_params(&params)?;
+        let ctx = whisper_rs::WhisperContext::new_with_params(
+            model_path,
+            whisper_rs::WhisperContextParameters::default(),
+        )
+        .context("failed to load whisper model")?;
+        let state = ctx.create_state().context("failed to create state")?;
+        Ok(Self {
+            token_eot: ctx.token_eot(),
+            ctx,
+            state,
+            params,
+            allowed_languages: Vec::new(),
+            task: Task::Transcribe,
+            cancel: CancelToken::default(),
+        })
+    }
+
+    pub fn set_task(&mut self, task: Task) {
+        self.task = task;
+    }
+
+    /// When no language is given, detect it and pick the most likely one of
+    /// `languages` (ISO 639-1 codes like "en").
+    pub fn set_allowed_languages(&mut self, languages: &[String]) -> Result<()> {
+        self.allowed_languages = languages
+            .iter()
+            .map(|code| {
+                whisper_rs::get_lang_id(code)
+                    .with_context(|| format!("unknown language in languages: {code}"))
+            })
+            .collect::<Result<_>>()?;
+        Ok(())
+    }
+
+    fn threads(&self) -> usize {
+        self.params
+            .n_threads
+            .map(|n| n as usize)
+            .unwrap_or_else(default_threads)
+    }
+
+    fn detect_language(&mut self, audio: &[f32]) -> Result<&'static str> {
+        let threads = self.threads();
+        self.state
+            .pcm_to_mel(audio, threads)
+            .context("failed to compute mel spectrogram")?;
+        let (_, probs) = self
+            .state
+            .lang_detect(0, threads)
+            .context("language detection failed")?;
+        let id = most_likely_language(&probs, &self.allowed_languages)
+            .context("no allowed language detected")?;
+        whisper_rs::get_lang_str(id).context("unknown language id")
+    }
+}
+
+/// whisper.cpp's own default thread count
+fn default_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get().min(4))
+        .unwrap_or(4)
+}
+
+/// The id in `allowed` with the highest probability in `probs`.
+fn most_likely_language(probs: &[f32], allowed: &[i32]) -> Option<i32> {
+    allowed
+        .iter()
+        .filter_map(|&id| Some((id, *probs.get(id as usize)?)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(id, _)| id)
+}
+
+fn validate_params(params: &WhisperConfig) -> Result<()> {
+    if params.best_of == 0 {
+        bail!("whisper.best_of must be at least 1");
+    }
+    if params.beam_size == 0 {
+        bail!("whisper.beam_size must be at least 1");
+    }
+    if params.n_threads == Some(0) {
+        bail!("whisper.n_threads must be at least 1");
+    }
+    Ok(())
+}
+
+fn full_params<'a>(
+    config: &WhisperConfig,
+    language: Option<&'a str>,
+    initial_prompt: Option<&'a str>,
+) -> whisper_rs::FullParams<'a, 'a> {
+    let strategy = match config.strategy {
+        Strategy::Greedy => whisper_rs::SamplingStrategy::Greedy {
+            best_of: config.best_of as i32,
+        },
+        Strategy::Beam => whisper_rs::SamplingStrategy::BeamSearch {
+            beam_size: config.beam_size as i32,
+            patience: -1.0,
+        },
+    };
+    let mut params = whisper_rs::FullParams::new(strategy);
+    if let Some(lang) = language {
+        params.set_language(Some(lang));
+    }
+    if let Some(prompt) = initial_prompt {
+        params.set_initial_prompt(prompt);
+    }
+    if let Some(n) = config.n_threads {
+        params.set_n_threads(n as i32);
+    }
+    params.set_temperature(config.temperature);
+    params.set_temperature_inc(config.temperature_inc);
+    params.set_no_context(config.no_context);
+    params.set_suppress_blank(config.suppress_blank);
+    params.set_suppress_nst(config.suppress_non_speech_tokens);
+    params.set_single_segment(config.single_segment);
+    params.set_entropy_thold(config.entropy_thold);
+    params.set_logprob_thold(config.logprob_thold);
+    params.set_no_speech_thold(config.no_speech_thold);
+    params.set_token_timestamps(config.word_timestamps);
+    params
+}
+
+/// whisper.cpp abort callback; `flag` points at a `CancelToken`'s flag.
+unsafe extern "C" fn abort_requested(flag: *mut std::ffi::c_void) -> bool {
+    (*(flag as *const AtomicBool)).load(Ordering::Relaxed)
+}
+
+impl TranscribeBackend for LocalWhisper {
+    fn transcribe(
+        &mut self,
+        audio: &[f32],
+        language: Option<&str>,
+        initial_prompt: Option<&str>,
+    ) -> Result<Transcript> {
+        let language = match language {
+            None if !self.allowed_languages.is_empty() && !audio.is_empty() => {
+                Some(self.detect_language(audio)?)
+            }
+            other => other,
+        };
+        let mut params = full_params(&self.params, language, initial_prompt);
+        params.set_translate(self.task == Task::Translate);
+        // SAFETY: the flag is owned by `self.cancel`, which outlives the call
+        unsafe {
+            params.set_abort_callback(Some(abort_requested));
+            params.set_abort_callback_user_data(Arc::as_ptr(&self.cancel.0) as *mut _);
+        }
+        let result = self.state.full(params, audio);
+        if self.cancel.is_cancelled() {
+            bail!("transcription cancelled");
+        }
+        result.context("whisper inference failed")?;
+
+        let state = &self.state;
+        let n = state.full_n_segments().context("failed to get segments")?;
+        let mut segments = Vec::new();
+        for i in 0..n {
+            match state.full_get_segment_text(i) {
+                Ok(text) => {
+                    // whisper timestamps are in centiseconds
+                    let t0 = state.full_get_segment_t0(i).unwrap_or(0).max(0) as u64;
+                    let t1 = state.full_get_segment_t1(i).unwrap_or(0).max(0) as u64;
+                    let tokens = self.text_tokens(i);
+                    let words = if self.params.word_timestamps {
+                        group_words(&tokens)
+                    } else {
+                        Vec::new()
+                    };
+                    segments.push(Segment {
+                        text: text.trim().to_string(),
+                        start_ms: t0 * 10,
+                        end_ms: t1 * 10,
+                        avg_logprob: avg_logprob(&tokens),
+                        no_speech_prob: None,
+                        words,
+                    });
+                }
+                Err(e) => eprintln!("warning: segment {i} failed: {e}"),
+            }
+        }
+        let lang_id = state.full_lang_id_from_state().ok();
+        if !segments.is_empty() && audio.len() <= WINDOW_SAMPLES {
+            match self.no_speech_prob(lang_id) {
+                Ok(prob) => segments
+                    .iter_mut()
+                    .for_each(|s| s.no_speech_prob = Some(prob)),
+                Err(e) => eprintln!("warning: no-speech probability failed: {e}"),
+            }
+        }
+        let language = lang_id.and_then(whisper_rs::get_lang_str).map(String::from);
+        Ok(Transcript {
+            language,
+            segments,
+            ..Default::default()
+        })
+    }
+
+    fn warm_up(&mut self) -> Result<()> {
+        self.transcribe(&[0.0; WARM_UP_SAMPLES], None, None)?;
+        Ok(())
+    }
+
+    fn as_streaming(&mut self) -> Option<&mut dyn StreamingTranscribe> {
+        Some(self)
+    }
+
+    fn set_cancel_token(&mut self, token: CancelToken) {
+        self.cancel = token;
+    }
+}
+
+impl StreamingTranscribe for LocalWhisper {
+    fn transcribe_segments(
+        &mut self,
+        audio: &[f32],
+        language: Option<&str>,
+        initial_prompt: Option<&str>,
+    ) -> Result<Transcript> {
+        self.transcribe(audio, language, initial_prompt)
+    }
+}
+
+impl LocalWhisper {
+    /// Probability of the no-speech token after the start-of-transcript
+    /// prompt, as whisper.cpp computes it for each window it decodes.
+    /// whisper-rs only exposes whisper.cpp's own value through the context's
+    /// default state, which it never creates, so this decodes the prompt again
+    /// against the encoder output `full` left behind. That output covers the
+    /// last window only, so callers skip clips longer than one window.
+    fn no_speech_prob(&mut self, lang_id: Option<i32>) -> Result<f32> {
+        let mut prompt = vec![self.ctx.token_sot()];
+        if self.ctx.is_multilingual() {
+            if let Some(id) = lang_id {
+                prompt.push(self.ctx.token_lang(id));
+            }
+            prompt.push(match self.task {
+                Task::Transcribe => self.ctx.token_transcribe(),
+                Task::Translate => self.ctx.token_translate(),
+            });
+        }
+        let threads = self.threads();
+        self.state
+            .decode(&prompt, 0, threads)
+            .context("no-speech decode failed")?;
+        let logits = self.state.get_logits().context("no logits after decode")?;
+        let nosp = self.ctx.token_nosp() as usize;
+        softmax_at(logits, nosp).context("no-speech token out of range")
+    }
+}
+
+/// `softmax(logits)[i]`, computed stably.
+fn softmax_at(logits: &[f32], i: usize) -> Option<f32> {
+    let target = *logits.get(i)?;
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let sum: f32 = logits.iter().map(|&l| (l - max).exp()).sum();
+    Some((target - max).exp() / sum)
+}
+
+/// A decoded text token of a segment.
+struct Token {
+    bytes: Vec<u8>,
+    logprob: f32,
+    /// Centiseconds; negative when whisper did not compute token timestamps
+    t0: i64,
+    t1: i64,
+}
+
+impl LocalWhisper {
+    /// The text tokens of segment `i`, without timestamp and other special
+    /// tokens.
+    fn text_tokens(&self, i: i32) -> Vec<Token> {
+        let n = self.state.full_n_tokens(i).unwrap_or(0);
+        (0..n)
+            .filter_map(|t| {
+                let data = self.state.full_get_token_data(i, t).ok()?;
+                if data.id >= self.token_eot {
+                    return None;
+                }
+                Some(Token {
+                    bytes: self.state.full_get_token_bytes(i, t).ok()?,
+                    logprob: data.plog,
+                    t0: data.t0,
+                    t1: data.t1,
+                })
+            })
+            .collect()
+    }
+}
+
+fn avg_logprob(tokens: &[Token]) -> Option<f32> {
+    if tokens.is_empty() {
+        return None;
+    }
+    Some(tokens.iter().map(|t| t.logprob).sum::<f32>() / tokens.len() as f32)
+}
+
+/// Merge tokens into words: a token starting with a space begins a new word.
+/// Bytes are joined before decoding since one character can span tokens.
+fn group_words(tokens: &[Token]) -> Vec<Word> {
+    let mut words = Vec::new();
+    let mut bytes = Vec::new();
+    let (mut t0, mut t1) = (0, 0);
+    for token in tokens {
+        if token.bytes.starts_with(b" ") && !bytes.is_empty() {
+            push_word(&mut words, &bytes, t0, t1);
+            bytes.clear();
+        }
+        if bytes.is_empty() {
+            t0 = token.t0;
+        }
+        bytes.extend_from_slice(&token.bytes);
+        t1 = token.t1;
+    }
+    push_word(&mut words, &bytes, t0, t1);
+    words
+}
+
+fn push_word(words: &mut Vec<Word>, bytes: &[u8], t0: i64, t1: i64) {
+    let text = String::from_utf8_lossy(bytes).trim().to_string();
+    if !text.is_empty() {
+        words.push(Word {
+            text,
+            start_ms: t0.max(0) as u64 * 10,
+            end_ms: t1.max(0) as u64 * 10,
+        });
+    }
+}
+
+/// Join segment texts with single spaces.
+pub fn join_segments(segments: &[Segment]) -> String {
+    let mut text = String::new();
+    for segment in segments.iter().filter(|s| !s.text.is_empty()) {
+        text.push_str(&segment.text);
+        text.push(' ');
+    }
+    text.trim().to_string()
+}
+
+// --- Duration-routed local models ---
+
+/// Local whisper with a model per clip length, e.g. a small model that is
+/// instant on short commands and a large one for long dictation. Models are
+/// loaded on first use and then kept.
+pub struct ModelRouter {
+    /// Tried in order; the last one should be a catch-all
+    rules: Vec<ModelRule>,
+    params: WhisperConfig,
+    languages: Vec<String>,
+    task: Task,
+    cancel: CancelToken,
+    verbose: bool,
+    /// Loaded models by path
+    models: HashMap<String, LocalWhisper>,
+}
+
+impl ModelRouter {
+    pub fn new(rules: Vec<ModelRule>, params: WhisperConfig, verbose: bool) -> Self {
+        Self {
+            rules,
+            params,
+            languages: Vec::new(),
+            task: Task::Transcribe,
+            cancel: CancelToken::default(),
+            verbose,
+            models: HashMap::new(),
+        }
+    }
+
+    pub fn set_allowed_languages(&mut self, languages: &[String]) -> Result<()> {
+        // Checked now rather than when the first model loads
+        for code in languages {
+            whisper_rs::get_lang_id(code)
+                .with_context(|| format!("unknown language in languages: {code}"))?;
+        }
+        self.languages = languages.to_vec();
+        Ok(())
+    }
+
+    pub fn set_task(&mut self, task: Task) {
+        self.task = task;
+    }
+
+    /// Path of the first rule a clip of `samples` fits.
+    fn pick(&self, samples: usize) -> Result<&str> {
+        let secs = samples as f32 / 16_000.0;
+        self.rules
+            .iter()
+            .find(|rule| rule.max_secs.is_none_or(|max| secs <= max))
+            .map(|rule| rule.path.as_str())
+            .with_context(|| format!("no model configured for a {secs:.1}s clip"))
+    }
+
+    fn model(&mut self, path: &str) -> Result<&mut LocalWhisper> {
+        if !self.models.contains_key(path) {
+            if self.verbose {
+                eprintln!("loading model from {path}...");
+            }
+            let mut local = LocalWhisper::new(path, self.params.clone())?;
+            local.set_allowed_languages(&self.languages)?;
+            local.set_task(self.task);
+            local.set_cancel_token(self.cancel.clone());
+            self.models.insert(path.to_string(), local);
+        }
+        Ok(self.models.get_mut(path).expect("model was just loaded"))
+    }
+
+    /// Load the model for the shortest clips, which partial streaming passes
+    /// always use. Call it before streaming so the first partial text isn't
+    /// held up by loading a model.
+    pub fn load_streaming_model(&mut self) -> Result<()> {
+        let path = self.pick(0)?.to_string();
+        self.model(&path)?;
+        Ok(())
+    }
+
+    fn streaming_model(&mut self) -> Option<&mut LocalWhisper> {
+        let path = self.pick(0).ok()?.to_string();
+        self.models.get_mut(&path)
+    }
+}
+
+impl TranscribeBackend for ModelRouter {
+    fn transcribe(
+        &mut self,
+        audio: &[f32],
+        language: Option<&str>,
+        initial_prompt: Option<&str>,
+    ) -> Result<Transcript> {
+        let path = self.pick(audio.len())?.to_string();
+        self.model(&path)?
+            .transcribe(audio, language, initial_prompt)
+    }
+
+    /// Only the model for the shortest clips is loaded up front, since
+    /// that is where a slow first call shows the most.
+    fn warm_up(&mut self) -> Result<()> {
+        let path = self.pick(0)?.to_string();
+        self.model(&path)?.warm_up()
+    }
+
+    /// Only once the streaming model is loaded.
+    fn as_streaming(&mut self) -> Option<&mut dyn StreamingTranscribe> {
+        self.streaming_model()?;
+        Some(self)
+    }
+
+    fn set_cancel_token(&mut self, token: CancelToken) {
+        for model in self.models.values_mut() {
+            model.set_cancel_token(token.clone());
+        }
+        self.cancel = token;
+    }
+}
+
+impl StreamingTranscribe for ModelRouter {
+    /// Partial passes stay on the streaming model whatever the window length,
+    /// so a growing recording never switches models mid-stream.
+    fn transcribe_segments(
+        &mut self,
+        audio: &[f32],
+        language: Option<&str>,
+        initial_prompt: Option<&str>,
+    ) -> Result<Transcript> {
+        self.streaming_model()
+            .context("streaming model is not loaded")?
+            .transcribe(audio, language, initial_prompt)
+    }
+
+    fn keeps_partials(&mut self, samples: usize) -> bool {
+        self.pick(samples).ok() == self.pick(0).ok()
+    }
+}
+
+// --- Fallback chain ---
+
+/// Tries backends in order: when one fails (including timeouts) the same
+/// audio is handed to the next, so a dictation is only lost if all of them
+/// fail.
+pub struct BackendChain {
+    backends: Vec<(String, Box<dyn TranscribeBackend>)>,
+    cancel: CancelToken,
+}
+
+impl BackendChain {
+    pub fn new(backends: Vec<(String, Box<dyn TranscribeBackend>)>) -> Result<Self> {
+        if backends.is_empty() {
+            bail!("backend list is empty");
+        }
+        Ok(Self {
+            backends,
+            cancel: CancelToken::default(),
+        })
+    }
+}
+
+impl TranscribeBackend for BackendChain {
+    fn transcribe(
+        &mut self,
+        audio: &[f32],
+        language: Option<&str>,
+        initial_prompt: Option<&str>,
+    ) -> Result<Transcript> {
+        let fallback = self.backends.len() > 1;
+        let mut errors = Vec::new();
+        for (name, backend) in &mut self.backends {
+            match backend.transcribe(audio, language, initial_prompt) {
+                Ok(mut transcript) => {
+                    if fallback {
+                        transcript.backend = Some(name.clone());
+                    }
+                    return Ok(transcript);
+                }
+                // A cancelled job should not move on to the next backend
+                Err(e) if fallback && !self.cancel.is_cancelled() => {
+                    eprintln!("warning: {name} backend failed: {e}");
+                    errors.push(format!("{name}: {e}"));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        bail!("all backends failed ({})", errors.join("; "))
+    }
+
+    fn warm_up(&mut self) -> Result<()> {
+        for (_, backend) in &mut self.backends {
+            backend.warm_up()?;
+        }
+        Ok(())
+    }
+
+    /// Partial passes go to the first backend like every other request, so
+    /// streaming follows its capabilities.
+    fn as_streaming(&mut self) -> Option<&mut dyn StreamingTranscribe> {
+        self.backends[0].1.as_streaming()?;
+        Some(self)
+    }
+
+    fn set_cancel_token(&mut self, token: CancelToken) {
+        for (_, backend) in &mut self.backends {
+            backend.set_cancel_token(token.clone());
+        }
+        self.cancel = token;
+    }
+}
+
+impl StreamingTranscribe for BackendChain {
+    fn transcribe_segments(
+        &mut self,
+        audio: &[f32],
+        language: Option<&str>,
+        initial_prompt: Option<&str>,
+    ) -> Result<Transcript> {
+        self.backends[0]
+            .1
+            .as_streaming()
+            .context("backend does not support streaming")?
+            .transcribe_segments(audio, language, initial_prompt)
+    }
+
+    fn keeps_partials(&mut self, samples: usize) -> bool {
+        self.backends[0]
+            .1
+            .as_streaming()
+            .is_none_or(|backend| backend.keeps_partials(samples))
+    }
+}
+
+// --- OpenAI API backend ---
+
+pub struct ApiWhisper {
+    api_key: String,
+    api_url: String,
+    config: ApiConfig,
+    task: Task,
+    http: Http,
+}
+
+impl ApiWhisper {
+    pub fn new(api_key: String, api_url: String, config: ApiConfig) -> Result<Self> {
+        if config.audio_format == AudioFormat::OggOpus && !cfg!(feature = "opus") {
+            bail!("{OPUS_DISABLED}");
+        }
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in &config.headers {
+            let name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                .with_context(|| format!("invalid header name in [api] headers: {name}"))?;
+            let value = reqwest::header::HeaderValue::from_str(value)
+                .with_context(|| format!("invalid value for header {name}"))?;
+            headers.insert(name, value);
+        }
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_millis(config.connect_timeout_ms))
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .default_headers(headers)
+            .build()?;
+        let http = Http::new("API", client, config.retries, config.retry_backoff_ms)?;
+        Ok(Self {
+            api_key,
+            api_url,
+            config,
+            task: Task::Transcribe,
+            http,
+        })
+    }
+
+    /// Translating switches to the `/translations` endpoint next to the
+    /// configured `/transcriptions` one.
+    pub fn set_task(&mut self, task: Task) -> Result<()> {
+        if task == Task::Translate && self.task != Task::Translate {
+            self.api_url = translations_url(&self.api_url)?;
+        }
+        self.task = task;
+        Ok(())
+    }
+
+    fn form(
+        &self,
+        audio: &EncodedAudio,
+        language: Option<&str>,
+        initial_prompt: Option<&str>,
+    ) -> Result<reqwest::multipart::Form> {
+        let mut form = reqwest::multipart::Form::new()
+            .text("model", self.config.model.clone())
+            .text("response_format", self.config.response_format.as_str())
+            .part("file", audio.part()?);
+        // The translations endpoint has no language parameter
+        if let (Some(lang), Task::Transcribe) = (language, self.task) {
+            form = form.text("language", lang.to_string());
+        }
+        if let Some(prompt) = initial_prompt {
+            form = form.text("prompt", prompt.to_string());
+        }
+        if let Some(temperature) = self.config.temperature {
+            form = form.text("temperature", temperature.to_string());
+        }
+        for (name, value) in &self.config.fields {
+            form = form.text(name.clone(), value.clone());
+        }
+        Ok(form)
+    }
+}
+
+fn translations_url(transcriptions_url: &str) -> Result<String> {
+    match transcriptions_url
+        .trim_end_matches('/')
+        .strip_suffix("/transcriptions")
+    {
+        Some(base) => Ok(format!("{base}/translations")),
+        None => bail!(
+            "cannot derive a translations endpoint from api_url {transcriptions_url} \
+             (expected it to end in /transcriptions)"
+        ),
+    }
+}
+
+impl TranscribeBackend for ApiWhisper {
+    fn transcribe(
+        &mut self,
+        audio: &[f32],
+        language: Option<&str>,
+        initial_prompt: Option<&str>,
+    ) -> Result<Transcript> {
+        let duration_ms = audio.len() as u64 * 1000 / 16_000;
+        let encoded = encode_audio(audio, self.config.audio_format)?;
+        let body = self.http.post(&self.api_url, Some(&self.api_key), || {
+            self.form(&encoded, language, initial_prompt)
+        })?;
+        parse_response(self.config.response_format, &body, duration_ms)
+    }
+
+    fn set_cancel_token(&mut self, token: CancelToken) {
+        self.http.cancel = token;
+    }
+}
+
+// --- whisper.cpp server backend ---
+
+/// A whisper.cpp `whisper-server`, through its `/inference` endpoint.
+pub struct WhisperServer {
+    config: WhisperServerConfig,
+    task: Task,
+    http: Http,
+}
+
+impl WhisperServer {
+    pub fn new(config: WhisperServerConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()?;
+        let http = Http::new("whisper-server", client, config.retries, RETRY_BACKOFF_MS)?;
+        Ok(Self {
+            config,
+            task: Task::Transcribe,
+            http,
+        })
+    }
+
+    pub fn set_task(&mut self, task: Task) {
+        self.task = task;
+    }
+
+    fn form(
+        &self,
+        audio: &EncodedAudio,
+        language: Option<&str>,
+        initial_prompt: Option<&str>,
+    ) -> Result<reqwest::multipart::Form> {
+        let mut form = reqwest::multipart::Form::new()
+            .text("response_format", self.config.response_format.as_str())
+            .text("translate", (self.task == Task::Translate).to_string())
+            .part("file", audio.part()?);
+        // Without a language the server uses the one it was started with
+        if let Some(lang) = language {
+            form = form.text("language", lang.to_string());
+        }
+        if let Some(prompt) = initial_prompt {
+            form = form.text("prompt", prompt.to_string());
+        }
+        if let Some(temperature) = self.config.temperature {
+            form = form.text("temperature", temperature.to_string());
+        }
+        Ok(form)
+    }
+}
+
+impl TranscribeBackend for WhisperServer {
+    fn transcribe(
+        &mut self,
+        audio: &[f32],
+        language: Option<&str>,
+        initial_prompt: Option<&str>,
+    ) -> Result<Transcript> {
+        let duration_ms = audio.len() as u64 * 1000 / 16_000;
+        // The server only decodes WAV unless it was built with ffmpeg
+        let encoded = encode_audio(audio, AudioFormat::Wav)?;
+        let body = self.http.post(&self.config.url, None, || {
+            self.form(&encoded, language, initial_prompt)
+        })?;
+        // Some server versions report failures as a 200 with an error body
+        if self.config.response_format != ResponseFormat::Text {
+            if let Some(message) = json_error(&body) {
+                bail!("whisper-server error: {message}");
+            }
+        }
+        parse_response(self.config.response_format, &body, duration_ms)
+    }
+
+    fn set_cancel_token(&mut self, token: CancelToken) {
+        self.http.cancel = token;
+    }
+}
+
+// --- Wyoming protocol backend ---
+
+/// Audio is streamed in chunks of this many samples (100 ms)
+const WYOMING_CHUNK_SAMPLES: usize = 1600;
+
+/// An ASR service speaking the Wyoming protocol: newline-terminated JSON
+/// event headers, each followed by optional JSON data and binary payload.
+pub struct Wyoming {
+    config: WyomingConfig,
+    cancel: CancelToken,
+}
+
+impl Wyoming {
+    pub fn new(config: WyomingConfig) -> Self {
+        Self {
+            config,
+            cancel: CancelToken::default(),
+        }
+    }
+
+    /// Wyoming ASR has no translate task.
+    pub fn set_task(&mut self, task: Task) -> Result<()> {
+        if task == Task::Translate {
+            bail!("the wyoming backend cannot translate");
+        }
+        Ok(())
+    }
+
+    fn check_cancelled(&self) -> Result<()> {
+        if self.cancel.is_cancelled() {
+            bail!("transcription cancelled");
+        }
+        Ok(())
+    }
+
+    fn connect(&self) -> Result<TcpStream> {
+        let timeout = Duration::from_millis(self.config.timeout_ms);
+        let addr = self
+            .config
+            .address
+            .to_socket_addrs()
+            .with_context(|| format!("cannot resolve {}", self.config.address))?
+            .next()
+            .with_context(|| format!("no address for {}", self.config.address))?;
+        let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+            .with_context(|| format!("cannot connect to wyoming service at {addr}"))?;
+        // Reads wait in short slices so a cancel is noticed; see `Cancellable`
+        stream.set_read_timeout(Some(CANCEL_POLL))?;
+        stream.set_write_timeout(Some(timeout))?;
+        Ok(stream)
+    }
+}
+
+/// A socket whose reads give up when `cancel` is set, or after `timeout`
+/// without data. The socket's own read timeout must be short (`CANCEL_POLL`).
+struct Cancellable {
+    stream: TcpStream,
+    timeout: Duration,
+    cancel: CancelToken,
+}
+
+impl Read for Cancellable {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let start = std::time::Instant::now();
+        loop {
+            match self.stream.read(buf) {
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    if self.cancel.is_cancelled() {
+                        return Err(std::io::Error::other("transcription cancelled"));
+                    }
+                    if start.elapsed() >= self.timeout {
+                        return Err(e);
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl TranscribeBackend for Wyoming {
+    /// Wyoming has no prompt; `initial_prompt` is ignored.
+    fn transcribe(
+        &mut self,
+        audio: &[f32],
+        language: Option<&str>,
+        _initial_prompt: Option<&str>,
+    ) -> Result<Transcript> {
+        let duration_ms = audio.len() as u64 * 1000 / 16_000;
+        let stream = self.connect()?;
+        let mut writer = std::io::BufWriter::new(stream.try_clone()?);
+
+        let mut transcribe = serde_json::Map::new();
+        if let Some(model) = &self.config.model {
+            transcribe.insert("name".into(), model.clone().into());
+        }
+        if let Some(language) = language {
+            transcribe.insert("language".into(), language.into());
+        }
+        write_event(&mut writer, "transcribe", transcribe.into(), &[])?;
+        let format = serde_json::json!({ "rate": 16_000, "width": 2, "channels": 1 });
+        write_event(&mut writer, "audio-start", format.clone(), &[])?;
+        for chunk in audio.chunks(WYOMING_CHUNK_SAMPLES) {
+            self.check_cancelled()?;
+            let pcm: Vec<u8> = chunk
+                .iter()
+                .flat_map(|&s| to_i16(s).to_le_bytes())
+                .collect();
+            write_event(&mut writer, "audio-chunk", format.clone(), &pcm)?;
+        }
+        write_event(&mut writer, "audio-stop", serde_json::json!({}), &[])?;
+        writer.flush()?;
+
+        let mut reader = BufReader::new(Cancellable {
+            stream,
+            timeout: Duration::from_millis(self.config.timeout_ms),
+            cancel: self.cancel.clone(),
+        });
+        loop {
+            let event = read_event(&mut reader)?;
+            self.check_cancelled()?;
+            match event.kind.as_str() {
+                "transcript" => {
+                    let text = event.data["text"].as_str().unwrap_or_default();
+                    return Ok(Transcript {
+                        language: event.data["language"].as_str().and_then(language_code),
+                        segments: vec![Segment {
+                            text: text.trim().to_string(),
+                            start_ms: 0,
+                            end_ms: duration_ms,
+                            avg_logprob: None,
+                            no_speech_prob: None,
+                            words: Vec::new(),
+                        }],
+                        ..Default::default()
+                    });
+                }
+                "error" => bail!(
+                    "wyoming service error: {}",
+                    event.data["text"].as_str().unwrap_or("unknown error")
+                ),
+                // Streaming transcript chunks, pings and the like
+                _ => {}
+            }
+        }
+    }
+
+    fn set_cancel_token(&mut self, token: CancelToken) {
+        self.cancel = token;
+    }
+}
+
+/// One Wyoming event.
+#[derive(Debug)]
+struct Event {
+    kind: String,
+    data: serde_json::Value,
+    /// Binary payload; the client only reads it to skip past it
+    #[cfg_attr(not(test), allow(dead_code))]
+    payload: Vec<u8>,
+}
+
+fn write_event(
+    writer: &mut impl Write,
+    kind: &str,
+    data: serde_json::Value,
+    payload: &[u8],
+) -> Result<()> {
+    let mut header = serde_json::json!({ "type": kind, "data": data });
+    if !payload.is_empty() {
+        header["payload_length"] = payload.len().into();
+    }
+    serde_json::to_writer(&mut *writer, &header)?;
+    writer.write_all(b"\n")?;
+    writer.write_all(payload)?;
+    Ok(())
+}
+
+/// Read one event. Data may be inline in the header or, in newer protocol
+/// versions, follow it as `data_length` bytes of JSON.
+fn read_event(reader: &mut impl BufRead) -> Result<Event> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        bail!("wyoming service closed the connection");
+    }
+    let header: serde_json::Value =
+        serde_json::from_str(&line).context("invalid wyoming event header")?;
+    let kind = header["type"]
+        .as_str()
+        .context("wyoming event without a type")?
+        .to_string();
+    let mut data = match &header["data"] {
+        serde_json::Value::Object(map) => map.clone(),
+        _ => serde_json::Map::new(),
+    };
+    let read_section = |reader: &mut dyn Read, key: &str| -> Result<Vec<u8>> {
+        let len = header[key].as_u64().unwrap_or(0) as usize;
+        let mut buf = vec![0; len];
+        reader.read_exact(&mut buf)?;
+        Ok(buf)
+    };
+    let extra = read_section(reader, "data_length")?;
+    if !extra.is_empty() {
+        let extra: serde_json::Map<_, _> =
+            serde_json::from_slice(&extra).context("invalid wyoming event data")?;
+        data.extend(extra);
+    }
+    let payload = read_section(reader, "payload_length")?;
+    Ok(Event {
+        kind,
+        data: data.into(),
+        payload,
+    })
+}
+
+// --- External command backend ---
+
+/// Temp file names for `{file}`, unique within the process
+static COMMAND_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Runs a user program per recording: the WAV goes to its stdin (or to a temp
+/// file passed as `{file}`) and its stdout is the transcription.
+pub struct ExternalCommand {
+    argv: Vec<String>,
+    timeout: Duration,
+    task: Task,
+    /// Don't fail a successful run that wrote to stderr
+    ignore_stderr: bool,
+    cancel: CancelToken,
+}
+
+impl ExternalCommand {
+    pub fn new(argv: Vec<String>, timeout_ms: u64) -> Result<Self> {
+        if argv.is_empty() {
+            bail!("the command backend needs command = [\"program\", \"arg\", ...] in the config");
+        }
+        Ok(Self {
+            argv,
+            timeout: Duration::from_millis(timeout_ms),
+            task: Task::Transcribe,
+            ignore_stderr: false,
+            cancel: CancelToken::default(),
+        })
+    }
+
+    pub fn set_task(&mut self, task: Task) {
+        self.task = task;
+    }
+
+    pub fn set_ignore_stderr(&mut self, ignore: bool) {
+        self.ignore_stderr = ignore;
+    }
+
+    fn run(&self, args: &[String], stdin: Option<Vec<u8>>) -> Result<String> {
+        let mut child = Command::new(&args[0])
+            .args(&args[1..])
+            .stdin(if stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to run {}", args[0]))?;
+
+        // Feed and drain the pipes on threads so a chatty child can't block
+        let mut input = child.stdin.take();
+        let writer = std::thread::spawn(move || {
+            if let (Some(pipe), Some(bytes)) = (input.as_mut(), stdin) {
+                // The program may exit without reading everything
+                let _ = pipe.write_all(&bytes);
+            }
+        });
+        let stdout = read_pipe(child.stdout.take());
+        let stderr = read_pipe(child.stderr.take());
+
+        let start = std::time::Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if self.cancel.is_cancelled() {
+                let _ = child.kill();
+                let _ = child.wait();
+                bail!("transcription cancelled");
+            }
+            if start.elapsed() > self.timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                bail!(
+                    "{} timed out after {} ms",
+                    args[0],
+                    self.timeout.as_millis()
+                );
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        let _ = writer.join();
+        let stdout = stdout.join().unwrap_or_default();
+        let stderr = stderr.join().unwrap_or_default();
+        let stderr = String::from_utf8_lossy(&stderr);
+        match (status.success(), stderr.trim()) {
+            (false, "") => bail!("{} exited with {status}", args[0]),
+            (false, message) => bail!("{} exited with {status}: {message}", args[0]),
+            // A program that reports errors on stderr may still exit 0
+            (true, message) if !message.is_empty() && !self.ignore_stderr => {
+                bail!("{}: {message}", args[0])
+            }
+            (true, _) => {}
+        }
+        String::from_utf8(stdout).with_context(|| format!("{} printed invalid UTF-8", args[0]))
+    }
+}
+
+/// Write `wav` to a new private temp file. The file is created exclusively,
+/// so a name planted by another user (e.g. a symlink) is skipped rather
+/// than followed.
+fn create_temp_wav(wav: &[u8]) -> Result<PathBuf> {
+    loop {
+        let n = COMMAND_FILES.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("dictr-{}-{n}.wav", std::process::id()));
+        let mut file = match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to create {}", path.display()))
+            }
+        };
+        if let Err(e) = file.write_all(wav) {
+            let _ = std::fs::remove_file(&path);
+            return Err(e).with_context(|| format!("failed to write {}", path.display()));
+        }
+        return Ok(path);
+    }
+}
+
+fn read_pipe(pipe: Option<impl Read + Send + 'static>) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+impl TranscribeBackend for ExternalCommand {
+    fn transcribe(
+        &mut self,
+        audio: &[f32],
+        language: Option<&str>,
+        initial_prompt: Option<&str>,
+    ) -> Result<Transcript> {
+        let duration_ms = audio.len() as u64 * 1000 / 16_000;
+        let wav = encode_wav(audio)?;
+        let file = if self.argv.iter().any(|a| a.contains("{file}")) {
+            Some(create_temp_wav(&wav)?)
+        } else {
+            None
+        };
+        let task = match self.task {
+            Task::Transcribe => "transcribe",
+            Task::Translate => "translate",
+        };
+        let args: Vec<String> = self
+            .argv
+            .iter()
+            .map(|arg| {
+                let arg = arg
+                    .replace("{language}", language.unwrap_or("auto"))
+                    .replace("{prompt}", initial_prompt.unwrap_or(""))
+                    .replace("{task}", task);
+                match &file {
+                    Some(path) => arg.replace("{file}", &path.to_string_lossy()),
+                    None => arg,
+                }
+            })
+            .collect();
+
+        let output = match &file {
+            Some(path) => {
+                let output = self.run(&args, None);
+                let _ = std::fs::remove_file(path);
+                output?
+            }
+            None => self.run(&args, Some(wav))?,
+        };
+        Ok(Transcript {
+            segments: vec![Segment {
+                text: output.trim().to_string(),
+                start_ms: 0,
+                end_ms: duration_ms,
+                avg_logprob: None,
+                no_speech_prob: None,
+                words: Vec::new(),
+            }],
+            ..Default::default()
+        })
+    }
+
+    fn set_cancel_token(&mut self, token: CancelToken) {
+        self.cancel = token;
+    }
+}
+
+// --- HTTP plumbing shared by the server backends ---
+
+/// Connect timeout for backends without their own setting
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// First retry delay for backends without their own setting
+const RETRY_BACKOFF_MS: u64 = 500;
+
+/// How often an in-flight request checks whether it was cancelled
+const CANCEL_POLL: Duration = Duration::from_millis(20);
+
+/// Blocking wrapper around a reqwest client that retries transient failures
+/// with exponential backoff.
+struct Http {
+    /// Names the service in error messages, e.g. "API"
+    service: &'static str,
+    client: reqwest::Client,
+    rt: tokio::runtime::Runtime,
+    retries: u32,
+    retry_backoff_ms: u64,
+    /// Drops the request in flight, or the wait before a retry, when set
+    cancel: CancelToken,
+}
+
+impl Http {
+    fn new(
+        service: &'static str,
+        client: reqwest::Client,
+        retries: u32,
+        retry_backoff_ms: u64,
+    ) -> Result<Self> {
+        Ok(Self {
+            service,
+            client,
+            rt: tokio::runtime::Runtime::new()?,
+            retries,
+            retry_backoff_ms,
+            cancel: CancelToken::default(),
+        })
+    }
+
+    /// POST a multipart form and return the response body. `form` is called
+    /// again for every attempt since a sent form is consumed.
+    fn post(
+        &self,
+        url: &str,
+        bearer: Option<&str>,
+        form: impl Fn() -> Result<reqwest::multipart::Form>,
+    ) -> Result<String> {
+        let mut attempt = 0;
+        loop {
+            let mut request = self.client.post(url).multipart(form()?);
+            if let Some(token) = bearer {
+                request = request.bearer_auth(token);
+            }
+            match self.send(request) {
+                Ok(body) => return Ok(body),
+                Err(e) => match self.retry_delay(&e, attempt) {
+                    Some(delay) => {
+                        eprintln!(
+                            "warning: {}, retrying in {} ms",
+                            self.describe(&e),
+                            delay.as_millis()
+                        );
+                        self.sleep(delay).map_err(|e| anyhow!(self.describe(&e)))?;
+                        attempt += 1;
+                    }
+                    None => bail!(self.describe(&e)),
+                },
+            }
+        }
+    }
+
+    fn send(&self, request: reqwest::RequestBuilder) -> Result<String, RequestError> {
+        let cancel = self.cancel.clone();
+        self.rt.block_on(async move {
+            // Dropping the request future closes its connection
+            tokio::select! {
+                result = response(request) => result,
+                () = cancelled(&cancel) => Err(RequestError::Cancelled),
+            }
+        })
+    }
+
+    /// Wait before a retry, cut short by cancellation.
+    fn sleep(&self, delay: Duration) -> Result<(), RequestError> {
+        self.rt.block_on(async {
+            tokio::select! {
+                () = tokio::time::sleep(delay) => Ok(()),
+                () = cancelled(&self.cancel) => Err(RequestError::Cancelled),
+            }
+        })
+    }
+
+    /// `error` prefixed with the service it came from.
+    fn describe(&self, error: &RequestError) -> String {
+        match error {
+            RequestError::Cancelled => error.to_string(),
+            _ => format!("{} {error}", self.service),
+        }
+    }
+
+    /// How long to wait before retrying after `error`, or `None` when the
+    /// error is fatal or retries are used up.
+    fn retry_delay(&self, error: &RequestError, attempt: u32) -> Option<Duration> {
+        if attempt >= self.retries || !error.is_retryable() {
+            return None;
+        }
+        let delay = match error {
+            RequestError::Status {
+                retry_after: Some(delay),
+                ..
+            } => *delay,
+            _ => backoff(self.retry_backoff_ms, attempt),
+        };
+        // A server asking for a long pause is better served by the next backend
+        (delay <= MAX_RETRY_DELAY).then_some(delay)
+    }
+}
+
+async fn response(request: reqwest::RequestBuilder) -> Result<String, RequestError> {
+    let resp = request.send().await?;
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(resp.text().await?);
+    }
+    let retry_after = resp
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| parse_retry_after(v, SystemTime::now()));
+    let body = resp.text().await.unwrap_or_default();
+    Err(RequestError::Status {
+        status,
+        retry_after,
+        body,
+    })
+}
+
+/// A `Retry-After` value, in delta-seconds or as an HTTP date. A date in
+/// the past means retry now; an unparsable value is ignored.
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+/// Resolves once `token` is cancelled.
+async fn cancelled(token: &CancelToken) {
+    while !token.is_cancelled() {
+        tokio::time::sleep(CANCEL_POLL).await;
+    }
+}
+
+/// Longest wait before a retry. A server asking for a longer `Retry-After`
+/// gets no retry: the request fails at once so the next backend can run.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// A failed HTTP request; `Http` names the service when reporting it.
+#[derive(Debug)]
+enum RequestError {
+    /// No response: connection failure, timeout, broken body
+    Transport(reqwest::Error),
+    /// The server answered with an error status
+    Status {
+        status: reqwest::StatusCode,
+        retry_after: Option<Duration>,
+        body: String,
+    },
+    Cancelled,
+}
+
+impl RequestError {
+    /// Transient failures worth retrying; a bad key or request is not.
+    fn is_retryable(&self) -> bool {
+        match self {
+            RequestError::Transport(e) => !e.is_builder(),
+            RequestError::Status { status, .. } => {
+                *status == reqwest::StatusCode::REQUEST_TIMEOUT
+                    || *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                    || status.is_server_error()
+            }
+            RequestError::Cancelled => false,
+        }
+    }
+}
+
+impl std::fmt::Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestError::Transport(e) if e.is_timeout() => write!(f, "request timed out"),
+            RequestError::Transport(e) => write!(f, "request failed: {e}"),
+            RequestError::Status { status, body, .. } => {
+                write!(f, "returned {status}")?;
+                match error_message(body) {
+                    Some(message) => write!(f, ": {message}"),
+                    None => Ok(()),
+                }
+            }
+            RequestError::Cancelled => write!(f, "transcription cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for RequestError {}
+
+impl From<reqwest::Error> for RequestError {
+    fn from(e: reqwest::Error) -> Self {
+        RequestError::Transport(e)
+    }
+}
+
+/// The error of a JSON error body, or a short plain one.
+fn error_message(body: &str) -> Option<String> {
+    if let Some(message) = json_error(body) {
+        return Some(message);
+    }
+    let body = body.trim();
+    (!body.is_empty() && body.len() <= 200 && !body.contains('\n')).then(|| body.to_string())
+}
+
+/// `{"error": {"message": ...}}` (OpenAI) or `{"error": "..."}` (whisper-server)
+fn json_error(body: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(body).ok()?;
+    match &value["error"] {
+        serde_json::Value::String(message) => Some(message.clone()),
+        error => error["message"].as_str().map(String::from),
+    }
+}
+
+/// `base_ms * 2^attempt`, with the upper half randomized so clients that
+/// failed together don't retry in lockstep.
+fn backoff(base_ms: u64, attempt: u32) -> Duration {
+    let delay = base_ms.saturating_mul(1 << attempt.min(16));
+    let half = delay / 2;
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos() as u64);
+    Duration::from_millis(half + nanos % (half + 1))
+}
+
+/// Turn a response body into a transcript. Formats without timing report the
+/// whole clip as one segment.
+fn parse_response(format: ResponseFormat, body: &str, duration_ms: u64) -> Result<Transcript> {
+    #[derive(Deserialize)]
+    struct Response {
+        #[serde(default)]
+        text: String,
+        #[serde(default)]
+        language: Option<String>,
+        #[serde(default)]
+        segments: Vec<ResponseSegment>,
+    }
+    #[derive(Deserialize)]
+    struct ResponseSegment {
+        text: String,
+        start: f64,
+        end: f64,
+        #[serde(default)]
+        avg_logprob: Option<f32>,
+        #[serde(default)]
+        no_speech_prob: Option<f32>,
+        /// whisper-server includes word timing in each segment
+        #[serde(default)]
+        words: Vec<ResponseWord>,
+    }
+    #[derive(Deserialize)]
+    struct ResponseWord {
+        word: String,
+        start: f64,
+        end: f64,
+    }
+    let ms = |seconds: f64| (seconds * 1000.0).round() as u64;
+
+    let response = match format {
+        ResponseFormat::Text => Response {
+            text: body.to_string(),
+            language: None,
+            segments: Vec::new(),
+        },
+        ResponseFormat::Json | ResponseFormat::VerboseJson => {
+            serde_json::from_str(body).context("unexpected server response")?
+        }
+    };
+    let segments = if response.segments.is_empty() {
+        vec![Segment {
+            text: response.text.trim().to_string(),
+            start_ms: 0,
+            end_ms: duration_ms,
+            avg_logprob: None,
+            no_speech_prob: None,
+            words: Vec::new(),
+        }]
+    } else {
+        response
+            .segments
+            .into_iter()
+            .map(|s| Segment {
+                text: s.text.trim().to_string(),
+                start_ms: ms(s.start),
+                end_ms: ms(s.end),
+                avg_logprob: s.avg_logprob,
+                no_speech_prob: s.no_speech_prob,
+                words: s
+                    .words
+                    .into_iter()
+                    .map(|w| Word {
+                        text: w.word.trim().to_string(),
+                        start_ms: ms(w.start),
+                        end_ms: ms(w.end),
+                    })
+                    .collect(),
+            })
+            .collect()
+    };
+    Ok(Transcript {
+        language: response.language.as_deref().and_then(language_code),
+        segments,
+        ..Default::default()
+    })
+}
+
+/// verbose_json reports the language by name ("english"); whisper codes are
+/// passed through.
+fn language_code(language: &str) -> Option<String> {
+    let language = language.trim().to_lowercase();
+    if language.len() <= 3 {
+        return Some(language).filter(|l| !l.is_empty());
+    }
+    whisper_rs::get_lang_id(&language)
+        .and_then(whisper_rs::get_lang_str)
+        .map(String::from)
 }
 
-// --- Local whisper-rs backend ---
-
-pub struct LocalWhisper {
-    ctx: whisper_rs::WhisperContext,
+/// A recording encoded for upload.
+struct EncodedAudio {
+    bytes: Vec<u8>,
+    file_name: &'static str,
+    mime: &'static str,
 }
 
-impl LocalWhisper {
-    pub fn new(model_path: &str) -> Result<Self> {
-        let ctx = whisper_rs::WhisperContext::new_with_params(
-            model_path,
-            whisper_rs::WhisperContextParameters::default(),
-        )
-        .context("failed to load whisper model")?;
-        Ok(Self { ctx })
+impl EncodedAudio {
+    fn part(&self) -> Result<reqwest::multipart::Part> {
+        Ok(reqwest::multipart::Part::bytes(self.bytes.clone())
+            .file_name(self.file_name)
+            .mime_str(self.mime)?)
     }
 }
 
-impl TranscribeBackend for LocalWhisper {
-    fn transcribe(
-        &mut self,
-        audio: &[f32],
-        language: Option<&str>,
-        initial_prompt: Option<&str>,
-    ) -> Result<String> {
-        let mut state = self.ctx.create_state().context("failed to create state")?;
-        let mut params =
-            whisper_rs::FullParams::new(whisper_rs::SamplingStrategy::Greedy { best_of: 1 });
-        if let Some(lang) = language {
-            params.set_language(Some(lang));
-        }
-        if let Some(prompt) = initial_prompt {
-            params.set_initial_prompt(prompt);
+fn encode_audio(audio: &[f32], format: AudioFormat) -> Result<EncodedAudio> {
+    Ok(match format {
+        AudioFormat::Wav => EncodedAudio {
+            bytes: encode_wav(audio)?,
+            file_name: "audio.wav",
+            mime: "audio/wav",
+        },
+        AudioFormat::Flac => {
+            let samples: Vec<i16> = audio.iter().map(|&s| to_i16(s)).collect();
+            EncodedAudio {
+                bytes: flac::encode(&samples, 16_000),
+                file_name: "audio.flac",
+                mime: "audio/flac",
+            }
         }
-
-        state
-            .full(params, audio)
-            .context("whisper inference failed")?;
-
-        let n = state.full_n_segments().context("failed to get segments")?;
-        let mut text = String::new();
-        for i in 0..n {
-            match state.full_get_segment_text(i) {
-                Ok(seg) => {
-                    text.push_str(seg.trim());
-                    text.push(' ');
-                }
-                Err(e) => eprintln!("warning: segment {i} failed: {e}"),
+        AudioFormat::OggOpus => {
+            let samples: Vec<i16> = audio.iter().map(|&s| to_i16(s)).collect();
+            EncodedAudio {
+                bytes: encode_ogg_opus(&samples)?,
+                file_name: "audio.ogg",
+                mime: "audio/ogg",
             }
         }
-        Ok(text.trim().to_string())
-    }
+    })
 }
 
-// --- OpenAI API backend ---
-
-pub struct ApiWhisper {
-    api_key: String,
-    api_url: String,
-    client: reqwest::Client,
-    rt: tokio::runtime::Runtime,
+#[cfg(feature = "opus")]
+fn encode_ogg_opus(samples: &[i16]) -> Result<Vec<u8>> {
+    opus::encode(samples)
 }
 
-impl ApiWhisper {
-    pub fn new(api_key: String, api_url: String) -> Result<Self> {
-        let rt = tokio::runtime::Runtime::new()?;
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
-            .build()?;
-        Ok(Self {
-            api_key,
-            api_url,
-            client,
-            rt,
-        })
-    }
+#[cfg(not(feature = "opus"))]
+fn encode_ogg_opus(_samples: &[i16]) -> Result<Vec<u8>> {
+    bail!("{OPUS_DISABLED}")
 }
 
-impl TranscribeBackend for ApiWhisper {
-    fn transcribe(
-        &mut self,
-        audio: &[f32],
-        language: Option<&str>,
-        initial_prompt: Option<&str>,
-    ) -> Result<String> {
-        let wav_bytes = encode_wav(audio)?;
-        let api_key = self.api_key.clone();
-        let api_url = self.api_url.clone();
-        let client = self.client.clone();
-        let language = language.map(String::from);
-        let initial_prompt = initial_prompt.map(String::from);
-        self.rt.block_on(async move {
-            let part = reqwest::multipart::Part::bytes(wav_bytes)
-                .file_name("audio.wav")
-                .mime_str("audio/wav")?;
-            let mut form = reqwest::multipart::Form::new()
-                .text("model", "whisper-1")
-                .part("file", part);
-            if let Some(lang) = language {
-                form = form.text("language", lang);
-            }
-            if let Some(prompt) = initial_prompt {
-                form = form.text("prompt", prompt);
-            }
-
-            let resp = client
-                .post(&api_url)
-                .bearer_auth(&api_key)
-                .multipart(form)
-                .send()
-                .await?
-                .error_for_status()?;
-
-            #[derive(Deserialize)]
-            struct TranscriptionResponse {
-                #[serde(default)]
-                text: String,
-            }
-            let parsed: TranscriptionResponse = resp.json().await?;
-            let text = parsed.text;
-            Ok(text)
-        })
-    }
+const OPUS_DISABLED: &str = "audio_format = \"ogg-opus\" needs dictr built with `--features opus`";
+
+fn to_i16(sample: f32) -> i16 {
+    (sample * i16::MAX as f32).clamp(i16::MIN as f32, i16::MAX as f32) as i16
 }
 
 fn encode_wav(audio: &[f32]) -> Result<Vec<u8>> {
@@ -145,8 +1715,7 @@ fn encode_wav(audio: &[f32]) -> Result<Vec<u8>> {
     };
     let mut writer = hound::WavWriter::new(&mut buf, spec)?;
     for &sample in audio {
-        let s = (sample * i16::MAX as f32).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
-        writer.write_sample(s)?;
+        writer.write_sample(to_i16(sample))?;
     }
     writer.finalize()?;
     Ok(buf.into_inner())
@@ -202,23 +1771,382 @@ mod tests {
         assert_eq!(reader.len(), 0);
     }
 
+    #[test]
+    fn encode_audio_sets_name_and_mime() {
+        let audio = vec![0.25f32; 16000];
+        let wav = encode_audio(&audio, AudioFormat::Wav).unwrap();
+        assert_eq!((wav.file_name, wav.mime), ("audio.wav", "audio/wav"));
+        let flac = encode_audio(&audio, AudioFormat::Flac).unwrap();
+        assert_eq!((flac.file_name, flac.mime), ("audio.flac", "audio/flac"));
+        assert_eq!(&flac.bytes[..4], b"fLaC");
+        assert!(flac.bytes.len() < wav.bytes.len() / 10);
+    }
+
+    #[test]
+    fn encode_audio_flac_decodes_to_the_wav_samples() {
+        let audio: Vec<f32> = (0..16_123).map(|i| (i as f32 * 0.05).sin() * 0.5).collect();
+        let flac = encode_audio(&audio, AudioFormat::Flac).unwrap();
+        let mut reader = claxon::FlacReader::new(Cursor::new(&flac.bytes)).unwrap();
+        assert_eq!(reader.streaminfo().sample_rate, 16_000);
+        let decoded: Vec<i16> = reader.samples().map(|s| s.unwrap() as i16).collect();
+
+        let wav = encode_wav(&audio).unwrap();
+        let mut reader = hound::WavReader::new(Cursor::new(&wav)).unwrap();
+        let expected: Vec<i16> = reader.samples::<i16>().map(|s| s.unwrap()).collect();
+        assert_eq!(decoded, expected);
+    }
+
+    #[cfg(feature = "opus")]
+    #[test]
+    fn encode_audio_ogg_opus() {
+        let ogg = encode_audio(&[0.25f32; 16000], AudioFormat::OggOpus).unwrap();
+        assert_eq!((ogg.file_name, ogg.mime), ("audio.ogg", "audio/ogg"));
+        assert_eq!(&ogg.bytes[..4], b"OggS");
+    }
+
+    #[cfg(not(feature = "opus"))]
+    #[test]
+    fn ogg_opus_needs_the_feature() {
+        let config = ApiConfig {
+            audio_format: AudioFormat::OggOpus,
+            ..ApiConfig::default()
+        };
+        let err = ApiWhisper::new("sk-test".into(), "http://localhost".into(), config);
+        assert_eq!(err.err().unwrap().to_string(), OPUS_DISABLED);
+        assert!(encode_audio(&[0.0; 160], AudioFormat::OggOpus).is_err());
+    }
+
+    #[test]
+    fn validate_params_rejects_zero_counts() {
+        assert!(validate_params(&WhisperConfig::default()).is_ok());
+        let beam = WhisperConfig {
+            strategy: Strategy::Beam,
+            beam_size: 0,
+            ..WhisperConfig::default()
+        };
+        assert!(validate_params(&beam).is_err());
+        let threads = WhisperConfig {
+            n_threads: Some(0),
+            ..WhisperConfig::default()
+        };
+        assert!(validate_params(&threads).is_err());
+    }
+
+    fn token(text: &str, t0: i64, t1: i64) -> Token {
+        Token {
+            bytes: text.as_bytes().to_vec(),
+            logprob: 0.0,
+            t0,
+            t1,
+        }
+    }
+
+    #[test]
+    fn group_words_merges_subword_tokens() {
+        let tokens = [
+            token(" Re", 0, 10),
+            token("factor", 10, 30),
+            token(" the", 30, 40),
+            token(" parser", 40, 70),
+            token(".", 70, 72),
+        ];
+        let words = group_words(&tokens);
+        let texts: Vec<&str> = words.iter().map(|w| w.text.as_str()).collect();
+        assert_eq!(texts, ["Refactor", "the", "parser."]);
+        assert_eq!((words[0].start_ms, words[0].end_ms), (0, 300));
+        assert_eq!((words[2].start_ms, words[2].end_ms), (400, 720));
+    }
+
+    #[test]
+    fn group_words_joins_split_utf8() {
+        // "é" is 0xC3 0xA9, split across two tokens
+        let tokens = [
+            Token {
+                bytes: vec![b' ', b'c', b'a', b'f', 0xC3],
+                logprob: 0.0,
+                t0: 0,
+                t1: 5,
+            },
+            Token {
+                bytes: vec![0xA9],
+                logprob: 0.0,
+                t0: 5,
+                t1: 8,
+            },
+        ];
+        let words = group_words(&tokens);
+        assert_eq!(words.len(), 1);
+        assert_eq!(words[0].text, "café");
+        assert!(group_words(&[]).is_empty());
+    }
+
+    #[test]
+    fn most_likely_language_is_clamped_to_allowed() {
+        // Whisper ids: en=0, de=2, fr=6, pl=10
+        let mut probs = vec![0.0; 100];
+        probs[0] = 0.2;
+        probs[2] = 0.1;
+        probs[6] = 0.3;
+        probs[10] = 0.4;
+        assert_eq!(most_likely_language(&probs, &[0, 6, 2]), Some(6));
+        assert_eq!(most_likely_language(&probs, &[0, 2]), Some(0));
+        assert_eq!(most_likely_language(&probs, &[]), None);
+        // Out-of-range ids are ignored
+        assert_eq!(most_likely_language(&probs, &[500, 2]), Some(2));
+    }
+
+    #[test]
+    fn softmax_of_one_logit() {
+        let p = softmax_at(&[1.0, 2.0, 3.0], 2).unwrap();
+        assert!((p - 0.665_241).abs() < 1e-5, "{p}");
+        // Large logits must not overflow
+        assert!((softmax_at(&[1000.0, 1000.0], 0).unwrap() - 0.5).abs() < 1e-6);
+        assert_eq!(softmax_at(&[0.0], 1), None);
+    }
+
+    #[test]
+    fn avg_logprob_of_tokens() {
+        assert_eq!(avg_logprob(&[]), None);
+        let mut a = token(" a", 0, 0);
+        a.logprob = -0.2;
+        let mut b = token(" b", 0, 0);
+        b.logprob = -0.4;
+        assert!((avg_logprob(&[a, b]).unwrap() + 0.3).abs() < 1e-6);
+    }
+
+    fn seg(text: &str) -> Segment {
+        Segment {
+            text: text.into(),
+            start_ms: 0,
+            end_ms: 0,
+            avg_logprob: None,
+            no_speech_prob: None,
+            words: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn join_segments_trims_and_spaces() {
+        assert_eq!(join_segments(&[]), "");
+        assert_eq!(
+            join_segments(&[seg("Hello"), seg("world.")]),
+            "Hello world."
+        );
+        assert_eq!(join_segments(&[seg("Hello"), seg("")]), "Hello");
+        assert_eq!(join_segments(&[seg("a"), seg(""), seg("b")]), "a b");
+    }
+
+    /// Returns `text` as one segment, or fails when `text` is `None`.
+    struct FixedBackend {
+        text: Option<&'static str>,
+        calls: usize,
+    }
+
+    impl TranscribeBackend for FixedBackend {
+        fn transcribe(
+            &mut self,
+            _audio: &[f32],
+            _language: Option<&str>,
+            _initial_prompt: Option<&str>,
+        ) -> Result<Transcript> {
+            self.calls += 1;
+            let Some(text) = self.text else {
+                bail!("offline");
+            };
+            Ok(Transcript {
+                segments: vec![seg(text)],
+                ..Default::default()
+            })
+        }
+    }
+
+    fn fixed(name: &str, text: Option<&'static str>) -> (String, Box<dyn TranscribeBackend>) {
+        (name.into(), Box::new(FixedBackend { text, calls: 0 }))
+    }
+
+    #[test]
+    fn chain_falls_back_to_next_backend() {
+        let mut chain = BackendChain::new(vec![
+            fixed("api", None),
+            fixed("local", Some("hello")),
+            fixed("never", Some("unused")),
+        ])
+        .unwrap();
+        let transcript = chain.transcribe(&[0.0; 160], None, None).unwrap();
+        assert_eq!(transcript.text(), "hello");
+        assert_eq!(transcript.backend.as_deref(), Some("local"));
+    }
+
+    #[test]
+    fn chain_reports_every_failure() {
+        let mut chain = BackendChain::new(vec![fixed("api", None), fixed("local", None)]).unwrap();
+        let err = chain.transcribe(&[0.0; 160], None, None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "all backends failed (api: offline; local: offline)"
+        );
+    }
+
+    #[test]
+    fn single_backend_chain_is_transparent() {
+        let mut chain = BackendChain::new(vec![fixed("local", Some("hi"))]).unwrap();
+        let transcript = chain.transcribe(&[0.0; 160], None, None).unwrap();
+        assert_eq!(transcript.backend, None);
+        let mut chain = BackendChain::new(vec![fixed("local", None)]).unwrap();
+        assert_eq!(
+            chain.transcribe(&[], None, None).unwrap_err().to_string(),
+            "offline"
+        );
+        assert!(BackendChain::new(Vec::new()).is_err());
+    }
+
+    fn rule(max_secs: Option<f32>, path: &str) -> ModelRule {
+        ModelRule {
+            max_secs,
+            path: path.into(),
+        }
+    }
+
+    #[test]
+    fn router_picks_first_fitting_model() {
+        let router = ModelRouter::new(
+            vec![
+                rule(Some(5.0), "base.bin"),
+                rule(Some(30.0), "small.bin"),
+                rule(None, "medium.bin"),
+            ],
+            WhisperConfig::default(),
+            false,
+        );
+        assert_eq!(router.pick(0).unwrap(), "base.bin");
+        assert_eq!(router.pick(5 * 16_000).unwrap(), "base.bin");
+        assert_eq!(router.pick(5 * 16_000 + 1).unwrap(), "small.bin");
+        assert_eq!(router.pick(600 * 16_000).unwrap(), "medium.bin");
+
+        let router = ModelRouter::new(
+            vec![rule(Some(5.0), "base.bin")],
+            WhisperConfig::default(),
+            false,
+        );
+        assert_eq!(
+            router.pick(10 * 16_000).unwrap_err().to_string(),
+            "no model configured for a 10.0s clip"
+        );
+    }
+
+    #[test]
+    fn cancelled_chain_does_not_fall_back() {
+        let mut chain =
+            BackendChain::new(vec![fixed("api", None), fixed("local", Some("hi"))]).unwrap();
+        let token = CancelToken::default();
+        chain.set_cancel_token(token.clone());
+        token.cancel();
+        assert_eq!(
+            chain
+                .transcribe(&[0.0; 160], None, None)
+                .unwrap_err()
+                .to_string(),
+            "offline"
+        );
+    }
+
+    /// A token that gets cancelled `ms` milliseconds from now
+    fn cancel_after(ms: u64) -> CancelToken {
+        let token = CancelToken::default();
+        let later = token.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(ms));
+            later.cancel();
+        });
+        token
+    }
+
     #[test]
     fn api_whisper_new_stores_fields() {
         let api = ApiWhisper::new(
             "sk-test".into(),
             "https://example.com/v1/transcriptions".into(),
+            ApiConfig::default(),
         )
         .unwrap();
         assert_eq!(api.api_key, "sk-test");
         assert_eq!(api.api_url, "https://example.com/v1/transcriptions");
     }
 
+    #[test]
+    fn api_translate_uses_translations_endpoint() {
+        let mut api = ApiWhisper::new(
+            "sk-test".into(),
+            "https://api.openai.com/v1/audio/transcriptions".into(),
+            ApiConfig::default(),
+        )
+        .unwrap();
+        api.set_task(Task::Translate).unwrap();
+        assert_eq!(api.api_url, "https://api.openai.com/v1/audio/translations");
+        // Setting it again does not rewrite the URL twice
+        api.set_task(Task::Translate).unwrap();
+        assert_eq!(api.api_url, "https://api.openai.com/v1/audio/translations");
+
+        assert_eq!(
+            translations_url("http://localhost:8080/v1/audio/transcriptions/").unwrap(),
+            "http://localhost:8080/v1/audio/translations"
+        );
+        assert!(translations_url("http://localhost:8080/inference").is_err());
+    }
+
+    #[test]
+    fn api_whisper_rejects_invalid_headers() {
+        let mut config = ApiConfig::default();
+        config.headers.insert("Bad Header".into(), "x".into());
+        let api = ApiWhisper::new("sk-test".into(), "http://localhost/".into(), config);
+        assert!(api.is_err());
+    }
+
+    #[test]
+    fn parse_json_response_is_one_segment() {
+        let t = parse_response(ResponseFormat::Json, r#"{"text": " Hello. "}"#, 1500).unwrap();
+        assert_eq!(t.segments.len(), 1);
+        assert_eq!(t.text(), "Hello.");
+        assert_eq!(t.segments[0].end_ms, 1500);
+        assert_eq!(t.language, None);
+
+        let t = parse_response(ResponseFormat::Text, "Hello.\n", 1500).unwrap();
+        assert_eq!(t.text(), "Hello.");
+        assert!(parse_response(ResponseFormat::Json, "Hello.", 1500).is_err());
+    }
+
+    #[test]
+    fn parse_verbose_json_segments() {
+        let body = r#"{
+            "task": "transcribe",
+            "language": "fr",
+            "duration": 3.2,
+            "text": "Bonjour. Ça va ?",
+            "segments": [
+                {"id": 0, "start": 0.0, "end": 1.24, "text": " Bonjour.", "avg_logprob": -0.3,
+                 "no_speech_prob": 0.04},
+                {"id": 1, "start": 1.24, "end": 3.2, "text": " Ça va ?"}
+            ]
+        }"#;
+        let t = parse_response(ResponseFormat::VerboseJson, body, 3200).unwrap();
+        assert_eq!(t.language.as_deref(), Some("fr"));
+        assert_eq!(t.segments.len(), 2);
+        assert_eq!(t.segments[0].text, "Bonjour.");
+        assert_eq!((t.segments[0].start_ms, t.segments[0].end_ms), (0, 1240));
+        assert_eq!(t.segments[0].avg_logprob, Some(-0.3));
+        assert_eq!(t.segments[1].avg_logprob, None);
+        assert_eq!(t.segments[0].no_speech_prob, Some(0.04));
+        assert_eq!(t.segments[1].no_speech_prob, None);
+        assert_eq!(t.text(), "Bonjour. Ça va ?");
+    }
+
     #[test]
     fn api_whisper_connection_refused() {
         // Hitting a port with nothing listening should produce an error, not panic
         let mut api = ApiWhisper::new(
             "sk-test".into(),
             "http://127.0.0.1:1/v1/audio/transcriptions".into(),
+            fast_retries(0),
         )
         .unwrap();
         let audio = vec![0.0f32; 16000];
@@ -230,9 +2158,526 @@ mod tests {
     fn api_whisper_transcribe_with_language_and_prompt() {
         // Verify the method doesn't panic when language and prompt are provided
         // (connection will fail, but form construction should succeed)
-        let mut api = ApiWhisper::new("sk-test".into(), "http://127.0.0.1:1/nope".into()).unwrap();
+        let mut api = ApiWhisper::new(
+            "sk-test".into(),
+            "http://127.0.0.1:1/nope".into(),
+            fast_retries(0),
+        )
+        .unwrap();
         let audio = vec![0.0f32; 16000];
         let result = api.transcribe(&audio, Some("en"), Some("test prompt"));
         assert!(result.is_err()); // connection error, not a panic
     }
+
+    fn fast_retries(retries: u32) -> ApiConfig {
+        ApiConfig {
+            retries,
+            retry_backoff_ms: 1,
+            timeout_ms: 500,
+            ..ApiConfig::default()
+        }
+    }
+
+    type Requests = std::sync::Arc<std::sync::Mutex<Vec<String>>>;
+
+    /// Answers one request per entry of `responses`, in order, on a local
+    /// port. Returns the server's base URL and the requests it received.
+    fn mock_server(responses: Vec<String>) -> (String, Requests) {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Requests::default();
+        let received = requests.clone();
+        std::thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                // Read the whole request so the client sees the response
+                // rather than a reset connection
+                let mut request = Vec::new();
+                let mut buf = [0u8; 8192];
+                let complete = |request: &[u8]| {
+                    let text = String::from_utf8_lossy(request);
+                    let Some(header_end) = text.find("\r\n\r\n") else {
+                        return false;
+                    };
+                    let length = text[..header_end]
+                        .lines()
+                        .find_map(|l| {
+                            let (name, value) = l.split_once(':')?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse::<usize>().ok())?
+                        })
+                        .unwrap_or(0);
+                    request.len() >= header_end + 4 + length
+                };
+                while !complete(&request) {
+                    let n = stream.read(&mut buf).unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                }
+                received
+                    .lock()
+                    .unwrap()
+                    .push(String::from_utf8_lossy(&request).into_owned());
+                if response.is_empty() {
+                    // Never answer, to trigger the client timeout
+                    std::thread::sleep(Duration::from_secs(2));
+                    continue;
+                }
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        (url, requests)
+    }
+
+    fn served(requests: &Requests) -> usize {
+        requests.lock().unwrap().len()
+    }
+
+    fn api_at(url: &str, key: &str, retries: u32) -> ApiWhisper {
+        let url = format!("{url}/v1/audio/transcriptions");
+        ApiWhisper::new(key.into(), url, fast_retries(retries)).unwrap()
+    }
+
+    fn http(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    fn ok() -> String {
+        http(
+            "200 OK",
+            "Content-Type: application/json\r\n",
+            r#"{"text":"ok"}"#,
+        )
+    }
+
+    fn unavailable() -> String {
+        http("503 Service Unavailable", "Retry-After: 0\r\n", "")
+    }
+
+    #[test]
+    fn api_retries_transient_errors() {
+        let (url, requests) = mock_server(vec![
+            unavailable(),
+            http("429 Too Many Requests", "", ""),
+            ok(),
+        ]);
+        let mut api = api_at(&url, "sk-test", 2);
+        let transcript = api.transcribe(&[0.0; 1600], None, None).unwrap();
+        assert_eq!(transcript.text(), "ok");
+        assert_eq!(served(&requests), 3);
+    }
+
+    #[test]
+    fn api_gives_up_after_retries() {
+        let (url, requests) = mock_server(vec![unavailable(), unavailable()]);
+        let mut api = api_at(&url, "sk-test", 1);
+        let err = api.transcribe(&[0.0; 1600], None, None).unwrap_err();
+        assert_eq!(err.to_string(), "API returned 503 Service Unavailable");
+        assert_eq!(served(&requests), 2);
+    }
+
+    #[test]
+    fn api_request_is_dropped_when_cancelled() {
+        // Accepts the connection but never answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "http://{}/v1/audio/transcriptions",
+            listener.local_addr().unwrap()
+        );
+        std::thread::spawn(move || {
+            let _conn = listener.accept();
+            std::thread::sleep(Duration::from_secs(10));
+        });
+        let config = ApiConfig {
+            timeout_ms: 10_000,
+            ..ApiConfig::default()
+        };
+        let mut api = ApiWhisper::new("sk-test".into(), url, config).unwrap();
+        api.set_cancel_token(cancel_after(100));
+        let start = std::time::Instant::now();
+        let err = api.transcribe(&[0.0; 1600], None, None).unwrap_err();
+        assert_eq!(err.to_string(), "transcription cancelled");
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn api_does_not_retry_fatal_errors() {
+        let (url, requests) = mock_server(vec![http(
+            "401 Unauthorized",
+            "",
+            r#"{"error": {"message": "Incorrect API key"}}"#,
+        )]);
+        let mut api = api_at(&url, "sk-bad", 2);
+        let err = api.transcribe(&[0.0; 1600], None, None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "API returned 401 Unauthorized: Incorrect API key"
+        );
+        assert_eq!(served(&requests), 1);
+    }
+
+    #[test]
+    fn api_does_not_wait_for_long_retry_after() {
+        let busy = http("503 Service Unavailable", "Retry-After: 60\r\n", "");
+        let (url, requests) = mock_server(vec![busy]);
+        let mut api = api_at(&url, "sk-test", 2);
+        assert!(api.transcribe(&[0.0; 1600], None, None).is_err());
+        assert_eq!(served(&requests), 1);
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_dates() {
+        let now = httpdate::parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT").unwrap();
+        let parse = |value| parse_retry_after(value, now);
+        assert_eq!(parse(" 120 "), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse("Wed, 21 Oct 2015 07:28:05 GMT"),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(parse("Wed, 21 Oct 2015 07:27:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse("soon"), None);
+        assert_eq!(parse("-1"), None);
+    }
+
+    #[test]
+    fn api_times_out() {
+        let (url, requests) = mock_server(vec![String::new()]);
+        let mut api = api_at(&url, "sk-test", 0);
+        let err = api.transcribe(&[0.0; 1600], None, None).unwrap_err();
+        assert_eq!(err.to_string(), "API request timed out");
+        assert_eq!(served(&requests), 1);
+    }
+
+    fn whisper_server_at(url: &str) -> WhisperServer {
+        WhisperServer::new(WhisperServerConfig {
+            url: format!("{url}/inference"),
+            retries: 0,
+            ..WhisperServerConfig::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn whisper_server_maps_fields_and_segments() {
+        let body = r#"{
+            "task": "translate",
+            "duration": 2.0,
+            "text": " Hello world.",
+            "segments": [{
+                "id": 0, "text": " Hello world.", "start": 0.0, "end": 2.0,
+                "avg_logprob": -0.2, "no_speech_prob": 0.01,
+                "words": [
+                    {"word": " Hello", "start": 0.0, "end": 0.8, "probability": 0.9},
+                    {"word": " world.", "start": 0.9, "end": 2.0, "probability": 0.8}
+                ]
+            }]
+        }"#;
+        let (url, requests) = mock_server(vec![http("200 OK", "", body)]);
+        let mut server = whisper_server_at(&url);
+        server.set_task(Task::Translate);
+        let transcript = server
+            .transcribe(&[0.0; 32_000], Some("de"), Some("Rust"))
+            .unwrap();
+        assert_eq!(transcript.text(), "Hello world.");
+        let segment = &transcript.segments[0];
+        assert_eq!(segment.avg_logprob, Some(-0.2));
+        assert_eq!(segment.no_speech_prob, Some(0.01));
+        assert_eq!(segment.words.len(), 2);
+        assert_eq!(
+            (segment.words[1].text.as_str(), segment.words[1].start_ms),
+            ("world.", 900)
+        );
+
+        let request = requests.lock().unwrap()[0].clone();
+        assert!(request.starts_with("POST /inference "));
+        let field = |name: &str, value: &str| {
+            request.contains(&format!("name=\"{name}\"\r\n\r\n{value}\r\n"))
+        };
+        assert!(field("response_format", "verbose_json"));
+        assert!(field("translate", "true"));
+        assert!(field("language", "de"));
+        assert!(field("prompt", "Rust"));
+        assert!(request.contains("filename=\"audio.wav\""));
+        assert!(!request.to_lowercase().contains("authorization:"));
+    }
+
+    #[test]
+    fn whisper_server_error_body_fails() {
+        let body = r#"{"error": "failed to read WAV file"}"#;
+        let (url, _) = mock_server(vec![http("200 OK", "", body)]);
+        let err = whisper_server_at(&url)
+            .transcribe(&[0.0; 1600], None, None)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "whisper-server error: failed to read WAV file"
+        );
+
+        let (url, _) = mock_server(vec![unavailable()]);
+        let err = whisper_server_at(&url)
+            .transcribe(&[0.0; 1600], None, None)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "whisper-server returned 503 Service Unavailable"
+        );
+    }
+
+    /// A Wyoming ASR service that records the events of one session and
+    /// answers with `reply` events once audio has stopped.
+    fn fake_wyoming(
+        reply: Vec<(&'static str, serde_json::Value)>,
+    ) -> (String, std::sync::mpsc::Receiver<Vec<Event>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut events = Vec::new();
+            loop {
+                let event = read_event(&mut reader).unwrap();
+                let done = event.kind == "audio-stop";
+                events.push(event);
+                if done {
+                    break;
+                }
+            }
+            for (kind, data) in reply {
+                // Newer servers send data as a separate section
+                let data = serde_json::to_vec(&data).unwrap();
+                let header = serde_json::json!({ "type": kind, "data_length": data.len() });
+                writeln!(writer, "{header}").unwrap();
+                writer.write_all(&data).unwrap();
+            }
+            tx.send(events).unwrap();
+        });
+        (address, rx)
+    }
+
+    fn wyoming_at(address: String) -> Wyoming {
+        Wyoming::new(WyomingConfig {
+            address,
+            model: Some("tiny-int8".into()),
+            timeout_ms: 2000,
+        })
+    }
+
+    #[test]
+    fn wyoming_streams_audio_and_reads_transcript() {
+        let (address, rx) = fake_wyoming(vec![
+            ("transcript-start", serde_json::json!({})),
+            (
+                "transcript",
+                serde_json::json!({ "text": " Turn on the lights. ", "language": "en" }),
+            ),
+        ]);
+        let audio: Vec<f32> = (0..4000)
+            .map(|i| if i == 1600 { 0.5 } else { 0.0 })
+            .collect();
+        let transcript = wyoming_at(address)
+            .transcribe(&audio, Some("en"), Some("ignored"))
+            .unwrap();
+        assert_eq!(transcript.text(), "Turn on the lights.");
+        assert_eq!(transcript.language.as_deref(), Some("en"));
+        assert_eq!(transcript.segments[0].end_ms, 250);
+
+        let events = rx.recv().unwrap();
+        let kinds: Vec<&str> = events.iter().map(|e| e.kind.as_str()).collect();
+        assert_eq!(
+            kinds,
+            [
+                "transcribe",
+                "audio-start",
+                "audio-chunk",
+                "audio-chunk",
+                "audio-chunk",
+                "audio-stop"
+            ]
+        );
+        assert_eq!(events[0].data["name"], "tiny-int8");
+        assert_eq!(events[0].data["language"], "en");
+        assert_eq!(events[1].data["rate"], 16_000);
+        // 4000 samples of s16le in 1600-sample chunks
+        let sizes: Vec<usize> = events[2..5].iter().map(|e| e.payload.len()).collect();
+        assert_eq!(sizes, [3200, 3200, 1600]);
+        // The non-silent sample starts the second chunk
+        assert_eq!(&events[3].payload[..2], &to_i16(0.5).to_le_bytes());
+    }
+
+    #[test]
+    fn wyoming_error_event_fails() {
+        let (address, _rx) = fake_wyoming(vec![(
+            "error",
+            serde_json::json!({ "text": "model not loaded", "code": "asr" }),
+        )]);
+        let err = wyoming_at(address)
+            .transcribe(&[0.0; 1600], None, None)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "wyoming service error: model not loaded");
+    }
+
+    #[test]
+    fn wyoming_cancel_interrupts_waiting_for_the_transcript() {
+        // Accepts the connection, then never answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let _stream = listener.accept().unwrap();
+            std::thread::sleep(Duration::from_secs(5));
+        });
+        let mut wyoming = Wyoming::new(WyomingConfig {
+            address,
+            model: None,
+            timeout_ms: 10_000,
+        });
+        wyoming.set_cancel_token(cancel_after(100));
+        let start = std::time::Instant::now();
+        let err = wyoming.transcribe(&[0.0; 1600], None, None).unwrap_err();
+        assert_eq!(err.to_string(), "transcription cancelled");
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn wyoming_rejects_translate() {
+        let mut wyoming = Wyoming::new(WyomingConfig::default());
+        assert!(wyoming.set_task(Task::Translate).is_err());
+        assert!(wyoming.set_task(Task::Transcribe).is_ok());
+    }
+
+    fn command(argv: &[&str], timeout_ms: u64) -> ExternalCommand {
+        ExternalCommand::new(argv.iter().map(|a| a.to_string()).collect(), timeout_ms).unwrap()
+    }
+
+    #[test]
+    fn command_reads_wav_from_stdin() {
+        let mut cmd = command(&["sh", "-c", "wc -c"], 5000);
+        let transcript = cmd.transcribe(&[0.0; 1000], None, None).unwrap();
+        // 44-byte header plus 2 bytes per sample
+        assert_eq!(transcript.text(), "2044");
+        assert_eq!(transcript.segments[0].end_ms, 62);
+    }
+
+    #[test]
+    fn command_substitutes_placeholders() {
+        let mut cmd = command(
+            &[
+                "sh",
+                "-c",
+                "echo \"$0|$1|$2\"",
+                "{language}",
+                "{prompt}",
+                "{task}",
+            ],
+            5000,
+        );
+        let text = cmd.transcribe(&[], None, None).unwrap().text();
+        assert_eq!(text, "auto||transcribe");
+        cmd.set_task(Task::Translate);
+        let text = cmd
+            .transcribe(&[], Some("fr"), Some("NixOS"))
+            .unwrap()
+            .text();
+        assert_eq!(text, "fr|NixOS|translate");
+    }
+
+    #[test]
+    fn command_file_placeholder_gets_a_temp_wav() {
+        let mut cmd = command(
+            &[
+                "sh",
+                "-c",
+                "head -c 4 \"$0\"; echo \" $(stat -c %a \"$0\") $0\"",
+                "{file}",
+            ],
+            5000,
+        );
+        let text = cmd.transcribe(&[0.0; 160], None, None).unwrap().text();
+        let [magic, mode, path] = text.splitn(3, ' ').collect::<Vec<_>>()[..] else {
+            panic!("unexpected output: {text}");
+        };
+        assert_eq!(magic, "RIFF");
+        assert_eq!(mode, "600", "only the owner can read the recording");
+        assert!(path.ends_with(".wav"));
+        assert!(!std::path::Path::new(path).exists(), "temp file is removed");
+    }
+
+    #[test]
+    fn temp_wav_skips_existing_names() {
+        let dir = std::env::temp_dir();
+        let target = dir.join(format!("dictr-target-{}", std::process::id()));
+        std::fs::write(&target, b"keep").unwrap();
+        let next = COMMAND_FILES.load(Ordering::Relaxed);
+        let planted: Vec<_> = (next..next + 3)
+            .map(|n| dir.join(format!("dictr-{}-{n}.wav", std::process::id())))
+            .collect();
+        for link in &planted {
+            std::os::unix::fs::symlink(&target, link).unwrap();
+        }
+
+        let path = create_temp_wav(b"RIFF").unwrap();
+        let written = std::fs::read(&path).unwrap();
+        let kept = std::fs::read(&target).unwrap();
+        for file in planted.iter().chain([&path, &target]) {
+            let _ = std::fs::remove_file(file);
+        }
+        assert!(!planted.contains(&path));
+        assert_eq!(written, b"RIFF");
+        assert_eq!(kept, b"keep", "symlink target is untouched");
+    }
+
+    #[test]
+    fn command_failure_reports_stderr() {
+        let mut cmd = command(&["sh", "-c", "echo model missing >&2; exit 3"], 5000);
+        let err = cmd.transcribe(&[0.0; 160], None, None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "sh exited with exit status: 3: model missing"
+        );
+
+        let mut cmd = command(&["sh", "-c", "echo hi; echo oops >&2"], 5000);
+        let err = cmd.transcribe(&[0.0; 160], None, None).unwrap_err();
+        assert_eq!(err.to_string(), "sh: oops");
+        cmd.set_ignore_stderr(true);
+        let text = cmd.transcribe(&[0.0; 160], None, None).unwrap().text();
+        assert_eq!(text, "hi");
+
+        let mut cmd = command(&["dictr-no-such-program"], 5000);
+        assert!(cmd.transcribe(&[0.0; 160], None, None).is_err());
+        assert!(ExternalCommand::new(Vec::new(), 5000).is_err());
+    }
+
+    #[test]
+    fn command_times_out() {
+        let mut cmd = command(&["sleep", "5"], 100);
+        let err = cmd.transcribe(&[0.0; 160], None, None).unwrap_err();
+        assert_eq!(err.to_string(), "sleep timed out after 100 ms");
+    }
+
+    #[test]
+    fn command_is_killed_when_cancelled() {
+        let mut cmd = command(&["sleep", "5"], 5000);
+        cmd.set_cancel_token(cancel_after(100));
+        let start = std::time::Instant::now();
+        let err = cmd.transcribe(&[0.0; 160], None, None).unwrap_err();
+        assert_eq!(err.to_string(), "transcription cancelled");
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn backoff_doubles_with_jitter() {
+        for attempt in 0..4 {
+            let full = 100 << attempt;
+            let delay = backoff(100, attempt).as_millis() as u64;
+            assert!((full / 2..=full).contains(&delay), "{attempt}: {delay}");
+        }
+    }
 }
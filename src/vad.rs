@@ -0,0 +1,310 @@
+use std::ops::Range;
+
+use crate::config::VadConfig;
+
+/// 30 ms at 16 kHz
+const FRAME_LEN: usize = 480;
+const FRAME_MS: u64 = 30;
+/// Force a cut in continuous mode so one utterance fits whisper's 30 s window
+const MAX_UTTERANCE_LEN: usize = 16_000 * 30;
+
+/// Find the span of `audio` (16 kHz mono) that contains speech, widened by
+/// `padding_ms` on both sides. Returns `None` when no speech is found.
+pub fn speech_range(audio: &[f32], config: &VadConfig) -> Option<Range<usize>> {
+    let flags: Vec<bool> = audio
+        .chunks(FRAME_LEN)
+        .map(|frame| is_speech(frame, config.threshold))
+        .collect();
+
+    let speech_frames = flags.iter().filter(|f| **f).count();
+    // Total speech, summed over all frames, needed to be worth transcribing;
+    // filters out key clicks
+    let min_frames = (config.min_speech_ms / FRAME_MS).max(1) as usize;
+    if speech_frames < min_frames {
+        return None;
+    }
+
+    let first = flags.iter().position(|f| *f)?;
+    let last = flags.iter().rposition(|f| *f)?;
+    let padding = (config.padding_ms / FRAME_MS) as usize * FRAME_LEN;
+    let start = (first * FRAME_LEN).saturating_sub(padding);
+    let end = ((last + 1) * FRAME_LEN + padding).min(audio.len());
+    Some(start..end)
+}
+
+/// A frame is speech if it is loud enough, or moderately loud with a high
+/// zero-crossing rate (unvoiced consonants like "s" and "f").
+pub fn is_speech(frame: &[f32], threshold: f32) -> bool {
+    if frame.is_empty() {
+        return false;
+    }
+    let rms = (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt();
+    if rms >= threshold {
+        return true;
+    }
+    rms >= threshold * 0.5 && zero_crossing_rate(frame) >= 0.25
+}
+
+fn zero_crossing_rate(frame: &[f32]) -> f32 {
+    if frame.len() < 2 {
+        return 0.0;
+    }
+    let crossings = frame
+        .windows(2)
+        .filter(|w| (w[0] >= 0.0) != (w[1] >= 0.0))
+        .count();
+    crossings as f32 / (frame.len() - 1) as f32
+}
+
+/// Splits a continuous 16 kHz stream into utterances separated by pauses of
+/// at least `silence_ms`.
+pub struct Segmenter {
+    threshold: f32,
+    padding_len: usize,
+    silence_frames: usize,
+    min_speech_frames: usize,
+    /// Samples not yet making up a whole frame
+    partial: Vec<f32>,
+    /// Current utterance, or the pre-speech padding while waiting for speech
+    current: Vec<f32>,
+    in_speech: bool,
+    speech_frames: usize,
+    silent_run: usize,
+}
+
+impl Segmenter {
+    pub fn new(config: &VadConfig) -> Self {
+        Self {
+            threshold: config.threshold,
+            padding_len: (config.padding_ms / FRAME_MS) as usize * FRAME_LEN,
+            silence_frames: (config.silence_ms / FRAME_MS).max(1) as usize,
+            min_speech_frames: (config.min_speech_ms / FRAME_MS).max(1) as usize,
+            partial: Vec::new(),
+            current: Vec::new(),
+            in_speech: false,
+            speech_frames: 0,
+            silent_run: 0,
+        }
+    }
+
+    /// Feed audio, returning every utterance completed by it.
+    pub fn push(&mut self, samples: &[f32]) -> Vec<Vec<f32>> {
+        self.partial.extend_from_slice(samples);
+        let whole = self.partial.len() / FRAME_LEN * FRAME_LEN;
+        let frames: Vec<f32> = self.partial.drain(..whole).collect();
+        frames
+            .chunks(FRAME_LEN)
+            .filter_map(|frame| self.push_frame(frame))
+            .collect()
+    }
+
+    /// Drop any utterance in progress.
+    pub fn reset(&mut self) {
+        self.partial.clear();
+        self.current.clear();
+        self.in_speech = false;
+        self.speech_frames = 0;
+        self.silent_run = 0;
+    }
+
+    fn push_frame(&mut self, frame: &[f32]) -> Option<Vec<f32>> {
+        let speech = is_speech(frame, self.threshold);
+        self.current.extend_from_slice(frame);
+
+        if !self.in_speech {
+            if speech {
+                self.in_speech = true;
+                self.speech_frames = 1;
+                self.silent_run = 0;
+            } else {
+                self.trim_to_padding();
+            }
+            return None;
+        }
+
+        if speech {
+            self.speech_frames += 1;
+            self.silent_run = 0;
+        } else {
+            self.silent_run += 1;
+        }
+        if self.silent_run >= self.silence_frames || self.current.len() >= MAX_UTTERANCE_LEN {
+            return self.finish_utterance();
+        }
+        None
+    }
+
+    fn finish_utterance(&mut self) -> Option<Vec<f32>> {
+        // Keep only `padding` worth of the trailing silence
+        let trailing = (self.silent_run * FRAME_LEN).saturating_sub(self.padding_len);
+        let end = self.current.len() - trailing;
+        let utterance: Vec<f32> = self.current.drain(..end).collect();
+        let enough = self.speech_frames >= self.min_speech_frames;
+
+        self.in_speech = false;
+        self.speech_frames = 0;
+        self.silent_run = 0;
+        self.trim_to_padding();
+
+        enough.then_some(utterance)
+    }
+
+    fn trim_to_padding(&mut self) {
+        let excess = self.current.len().saturating_sub(self.padding_len);
+        self.current.drain(..excess);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(n: usize, amplitude: f32) -> Vec<f32> {
+        (0..n)
+            .map(|i| (2.0 * std::f32::consts::PI * 220.0 * i as f32 / 16000.0).sin() * amplitude)
+            .collect()
+    }
+
+    fn config() -> VadConfig {
+        VadConfig {
+            enabled: true,
+            padding_ms: 90,
+            ..VadConfig::default()
+        }
+    }
+
+    #[test]
+    fn silence_has_no_speech() {
+        assert_eq!(speech_range(&vec![0.0; 16000], &config()), None);
+        assert_eq!(speech_range(&[], &config()), None);
+    }
+
+    #[test]
+    fn low_noise_floor_has_no_speech() {
+        // Alternating +/- 0.001 has maximal ZCR but is far below threshold
+        let noise: Vec<f32> = (0..16000)
+            .map(|i| if i % 2 == 0 { 0.001 } else { -0.001 })
+            .collect();
+        assert_eq!(speech_range(&noise, &config()), None);
+    }
+
+    #[test]
+    fn short_click_is_not_speech() {
+        let mut audio = vec![0.0; 16000];
+        audio[8000..8000 + FRAME_LEN].copy_from_slice(&tone(FRAME_LEN, 0.5));
+        assert_eq!(speech_range(&audio, &config()), None);
+    }
+
+    #[test]
+    fn trims_silent_edges_with_padding() {
+        // 0.5s silence, 1s tone, 0.5s silence
+        let mut audio = vec![0.0; 8000];
+        audio.extend(tone(16000, 0.3));
+        audio.extend(vec![0.0; 8000]);
+
+        let range = speech_range(&audio, &config()).unwrap();
+        let padding = 3 * FRAME_LEN;
+        let speech_start = 8000 / FRAME_LEN * FRAME_LEN;
+        assert_eq!(range.start, speech_start - padding);
+        assert!(range.end >= 24000 && range.end <= 24000 + padding + FRAME_LEN);
+        assert!(range.len() < audio.len());
+    }
+
+    #[test]
+    fn padding_clamped_to_buffer() {
+        let audio = tone(16000, 0.3);
+        assert_eq!(speech_range(&audio, &config()), Some(0..16000));
+    }
+
+    #[test]
+    fn quiet_fricative_counts_via_zero_crossings() {
+        let threshold = VadConfig::default().threshold;
+        // High-frequency hiss between half and full threshold
+        let hiss: Vec<f32> = (0..FRAME_LEN)
+            .map(|i| {
+                if i % 2 == 0 {
+                    threshold * 0.7
+                } else {
+                    -threshold * 0.7
+                }
+            })
+            .collect();
+        assert!(is_speech(&hiss, threshold));
+        // Same level at low frequency is not speech
+        let hum = tone(FRAME_LEN, threshold * 0.7 * std::f32::consts::SQRT_2);
+        assert!(!is_speech(&hum, threshold));
+    }
+
+    fn segmenter() -> Segmenter {
+        Segmenter::new(&VadConfig {
+            padding_ms: 90,
+            silence_ms: 300,
+            ..VadConfig::default()
+        })
+    }
+
+    #[test]
+    fn segmenter_splits_on_pauses() {
+        let mut seg = segmenter();
+        let mut audio = vec![0.0; 8000];
+        audio.extend(tone(8000, 0.3));
+        audio.extend(vec![0.0; 8000]);
+        audio.extend(tone(16000, 0.3));
+        audio.extend(vec![0.0; 8000]);
+
+        let utterances = seg.push(&audio);
+        assert_eq!(utterances.len(), 2);
+        // Speech plus up to padding on each side
+        let padding = 3 * FRAME_LEN;
+        assert!(utterances[0].len() >= 8000 && utterances[0].len() <= 8000 + 2 * padding + 960);
+        assert!(utterances[1].len() >= 16000 && utterances[1].len() <= 16000 + 2 * padding + 960);
+    }
+
+    #[test]
+    fn segmenter_handles_arbitrary_piece_sizes() {
+        let mut seg = segmenter();
+        let mut audio = tone(8000, 0.3);
+        audio.extend(vec![0.0; 8000]);
+
+        let mut utterances = Vec::new();
+        for piece in audio.chunks(333) {
+            utterances.extend(seg.push(piece));
+        }
+        assert_eq!(utterances.len(), 1);
+    }
+
+    #[test]
+    fn segmenter_waits_for_pause() {
+        let mut seg = segmenter();
+        assert!(seg.push(&tone(16000, 0.3)).is_empty());
+        // Pause shorter than silence_ms does not cut
+        assert!(seg.push(&[0.0; 3000]).is_empty());
+        assert!(seg.push(&tone(8000, 0.3)).is_empty());
+        assert_eq!(seg.push(&[0.0; 8000]).len(), 1);
+    }
+
+    #[test]
+    fn segmenter_drops_clicks() {
+        let mut seg = segmenter();
+        let mut audio = vec![0.0; 4000];
+        audio.extend(tone(FRAME_LEN, 0.5));
+        audio.extend(vec![0.0; 8000]);
+        assert!(seg.push(&audio).is_empty());
+    }
+
+    #[test]
+    fn segmenter_cuts_long_utterances() {
+        let mut seg = segmenter();
+        let utterances = seg.push(&tone(MAX_UTTERANCE_LEN + 16000, 0.3));
+        assert_eq!(utterances.len(), 1);
+        assert!(utterances[0].len() <= MAX_UTTERANCE_LEN);
+    }
+
+    #[test]
+    fn segmenter_reset_discards_utterance() {
+        let mut seg = segmenter();
+        assert!(seg.push(&tone(8000, 0.3)).is_empty());
+        seg.reset();
+        assert!(seg.push(&[0.0; 8000]).is_empty());
+    }
+}
@@ -0,0 +1,404 @@
+use anyhow::{anyhow, Result};
+use std::collections::VecDeque;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::filter::SegmentFilter;
+use crate::streaming::StreamingSession;
+use crate::transcribe::{CancelToken, TranscribeBackend, Transcript};
+
+/// A finished recording waiting to be transcribed.
+pub struct Job {
+    pub audio: Vec<f32>,
+    /// Finish the session passed to `start_streaming`, whose committed text
+    /// is already known, instead of transcribing from scratch
+    pub streamed: bool,
+}
+
+/// The outcome of one job.
+pub struct Done {
+    pub result: Result<Transcript>,
+    /// Time spent transcribing, not counting the wait in the queue
+    pub elapsed: Duration,
+    /// The job was cancelled; its result should be dropped
+    pub cancelled: bool,
+}
+
+/// What every job is transcribed with.
+struct Settings {
+    language: Option<String>,
+    initial_prompt: Option<String>,
+    filter: SegmentFilter,
+}
+
+/// Work for the background thread, run in the order it was sent.
+enum Task {
+    /// Use this session for the following partial passes and streamed job
+    Stream(StreamingSession),
+    /// Re-transcribe the recording so far for live partial text
+    Partial(Vec<f32>, CancelToken),
+    Job(Job, CancelToken),
+}
+
+/// Transcribes recordings on a background thread, one at a time and in the
+/// order they were submitted, so a new recording can start while earlier
+/// ones are still being transcribed. Streaming partial passes run there too,
+/// so the event loop never waits on inference.
+pub struct Worker {
+    tasks: Option<mpsc::Sender<Task>>,
+    done: mpsc::Receiver<(Result<Transcript>, Duration)>,
+    partials: mpsc::Receiver<Result<Option<String>>>,
+    /// Tokens of the jobs whose results have not been taken, oldest first
+    pending: VecDeque<CancelToken>,
+    /// Token of the partial pass whose result has not been taken
+    partial: Option<CancelToken>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    pub fn new<B: TranscribeBackend + 'static>(
+        backend: B,
+        language: Option<String>,
+        initial_prompt: Option<String>,
+        filter: SegmentFilter,
+    ) -> Self {
+        let settings = Settings {
+            language,
+            initial_prompt,
+            filter,
+        };
+        let (tasks, queue) = mpsc::channel();
+        let (results, done) = mpsc::channel();
+        let (partial_results, partials) = mpsc::channel();
+        let thread =
+            std::thread::spawn(move || run(backend, &settings, queue, results, partial_results));
+        Self {
+            tasks: Some(tasks),
+            done,
+            partials,
+            pending: VecDeque::new(),
+            partial: None,
+            thread: Some(thread),
+        }
+    }
+
+    fn send(&self, task: Task) {
+        if let Some(tasks) = &self.tasks {
+            // The thread only exits when the worker is dropped
+            let _ = tasks.send(task);
+        }
+    }
+
+    pub fn submit(&mut self, job: Job) {
+        // The recording is over, so its partial text no longer matters
+        self.cancel_partial();
+        let token = CancelToken::default();
+        self.pending.push_back(token.clone());
+        self.send(Task::Job(job, token));
+    }
+
+    /// Jobs submitted whose results have not been taken yet.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Abort the job in progress and drop the queued ones and any results
+    /// not taken yet. Returns how many jobs were cancelled.
+    pub fn cancel_all(&mut self) -> usize {
+        let mut cancelled = 0;
+        for token in &self.pending {
+            if !token.is_cancelled() {
+                token.cancel();
+                cancelled += 1;
+            }
+        }
+        cancelled
+    }
+
+    /// The result of the oldest pending job, once it is finished.
+    pub fn try_done(&mut self) -> Option<Done> {
+        let (result, elapsed) = self.done.try_recv().ok()?;
+        let token = self.pending.pop_front()?;
+        Some(Done {
+            result,
+            elapsed,
+            cancelled: token.is_cancelled(),
+        })
+    }
+
+    /// Begin a streamed recording; it ends with a job with `streamed` set.
+    pub fn start_streaming(&mut self, session: StreamingSession) {
+        self.cancel_partial();
+        self.send(Task::Stream(session));
+    }
+
+    /// Queue a partial pass over `audio`, the whole recording so far.
+    pub fn submit_partial(&mut self, audio: Vec<f32>) {
+        let token = CancelToken::default();
+        self.partial = Some(token.clone());
+        self.send(Task::Partial(audio, token));
+    }
+
+    /// A partial pass is queued or running.
+    pub fn partial_pending(&self) -> bool {
+        self.partial.is_some()
+    }
+
+    /// Abort the partial pass, if any; its result is dropped.
+    pub fn cancel_partial(&mut self) {
+        if let Some(token) = &self.partial {
+            token.cancel();
+        }
+    }
+
+    /// The partial text of the last pass, once it is finished: `None` inside
+    /// when the pass had nothing new to show.
+    pub fn try_partial(&mut self) -> Option<Result<Option<String>>> {
+        let result = self.partials.try_recv().ok()?;
+        let token = self.partial.take()?;
+        (!token.is_cancelled()).then_some(result)
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.cancel_all();
+        self.cancel_partial();
+        self.tasks = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run<B: TranscribeBackend>(
+    mut backend: B,
+    settings: &Settings,
+    queue: mpsc::Receiver<Task>,
+    results: mpsc::Sender<(Result<Transcript>, Duration)>,
+    partials: mpsc::Sender<Result<Option<String>>>,
+) {
+    let language = settings.language.as_deref();
+    let initial_prompt = settings.initial_prompt.as_deref();
+    let mut session = None;
+    for task in queue {
+        match task {
+            Task::Stream(new) => session = Some(new),
+            Task::Partial(audio, token) => {
+                let result = match session.as_mut() {
+                    Some(session) if !token.is_cancelled() => {
+                        backend.set_cancel_token(token);
+                        session.update(&mut backend, &audio, language, initial_prompt)
+                    }
+                    _ => Ok(None),
+                };
+                if partials.send(result).is_err() {
+                    break;
+                }
+            }
+            Task::Job(job, token) => {
+                let start = Instant::now();
+                let session = if job.streamed { session.take() } else { None };
+                let result = if token.is_cancelled() {
+                    Err(anyhow!("transcription cancelled"))
+                } else {
+                    backend.set_cancel_token(token);
+                    transcribe(&mut backend, job.audio, session, settings)
+                };
+                if results.send((result, start.elapsed())).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn transcribe(
+    backend: &mut dyn TranscribeBackend,
+    audio: Vec<f32>,
+    session: Option<StreamingSession>,
+    settings: &Settings,
+) -> Result<Transcript> {
+    let language = settings.language.as_deref();
+    let initial_prompt = settings.initial_prompt.as_deref();
+    match session {
+        Some(mut session) => session.finish(backend, &audio, language, initial_prompt),
+        None => {
+            let mut transcript = backend.transcribe(&audio, language, initial_prompt)?;
+            transcript.segments = settings.filter.apply(transcript.segments);
+            Ok(transcript)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FilterConfig;
+    use crate::transcribe::{Segment, StreamingTranscribe};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Answers with the clip length in samples. A clip of `BLOCK` samples
+    /// instead blocks until the job is cancelled.
+    #[derive(Default)]
+    struct FakeBackend {
+        calls: Arc<AtomicUsize>,
+        cancel: CancelToken,
+    }
+
+    const BLOCK: usize = 999;
+
+    impl TranscribeBackend for FakeBackend {
+        fn transcribe(
+            &mut self,
+            audio: &[f32],
+            _language: Option<&str>,
+            _initial_prompt: Option<&str>,
+        ) -> Result<Transcript> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            if audio.len() == BLOCK {
+                while !self.cancel.is_cancelled() {
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+                anyhow::bail!("transcription cancelled");
+            }
+            Ok(Transcript {
+                segments: vec![Segment {
+                    text: audio.len().to_string(),
+                    start_ms: 0,
+                    end_ms: 0,
+                    avg_logprob: None,
+                    no_speech_prob: None,
+                    words: Vec::new(),
+                }],
+                ..Default::default()
+            })
+        }
+
+        fn as_streaming(&mut self) -> Option<&mut dyn StreamingTranscribe> {
+            Some(self)
+        }
+
+        fn set_cancel_token(&mut self, token: CancelToken) {
+            self.cancel = token;
+        }
+    }
+
+    impl StreamingTranscribe for FakeBackend {
+        fn transcribe_segments(
+            &mut self,
+            audio: &[f32],
+            language: Option<&str>,
+            initial_prompt: Option<&str>,
+        ) -> Result<Transcript> {
+            self.transcribe(audio, language, initial_prompt)
+        }
+    }
+
+    fn filter() -> SegmentFilter {
+        SegmentFilter::new(&FilterConfig::default(), false)
+    }
+
+    fn worker() -> (Worker, Arc<AtomicUsize>) {
+        let backend = FakeBackend::default();
+        let calls = Arc::clone(&backend.calls);
+        (Worker::new(backend, None, None, filter()), calls)
+    }
+
+    fn submit(worker: &mut Worker, samples: usize) {
+        worker.submit(Job {
+            audio: vec![0.0; samples],
+            streamed: false,
+        });
+    }
+
+    /// Poll `f` until it returns something, failing after 5 s.
+    fn wait<T>(worker: &mut Worker, f: impl Fn(&mut Worker) -> Option<T>) -> T {
+        let start = Instant::now();
+        loop {
+            if let Some(value) = f(worker) {
+                return value;
+            }
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "job never finished"
+            );
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn results_come_back_in_submission_order() {
+        let (mut worker, _) = worker();
+        for samples in 1..=3 {
+            submit(&mut worker, samples);
+        }
+        assert_eq!(worker.pending(), 3);
+        for samples in 1..=3 {
+            let done = wait(&mut worker, Worker::try_done);
+            assert!(!done.cancelled);
+            assert_eq!(done.result.unwrap().text(), samples.to_string());
+        }
+        assert_eq!(worker.pending(), 0);
+        assert!(worker.try_done().is_none());
+    }
+
+    #[test]
+    fn cancel_aborts_running_job_and_skips_queued_ones() {
+        let (mut worker, calls) = worker();
+        submit(&mut worker, BLOCK);
+        submit(&mut worker, 1);
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(worker.cancel_all(), 2);
+        assert_eq!(worker.cancel_all(), 0);
+        for _ in 0..2 {
+            let done = wait(&mut worker, Worker::try_done);
+            assert!(done.cancelled);
+            assert!(done.result.is_err());
+        }
+        assert_eq!(calls.load(Ordering::Relaxed), 1, "queued job never ran");
+
+        // Later jobs get a fresh token
+        submit(&mut worker, 2);
+        let done = wait(&mut worker, Worker::try_done);
+        assert!(!done.cancelled);
+        assert_eq!(done.result.unwrap().text(), "2");
+    }
+
+    #[test]
+    fn partial_passes_run_on_the_worker() {
+        let (mut worker, _) = worker();
+        worker.start_streaming(StreamingSession::new(100, filter()));
+        worker.submit_partial(vec![0.0; 1600]);
+        assert!(worker.partial_pending());
+        let partial = wait(&mut worker, Worker::try_partial).unwrap();
+        assert_eq!(partial.as_deref(), Some("1600"));
+        assert!(!worker.partial_pending());
+
+        worker.submit(Job {
+            audio: vec![0.0; 3200],
+            streamed: true,
+        });
+        let done = wait(&mut worker, Worker::try_done);
+        assert_eq!(done.result.unwrap().text(), "3200");
+    }
+
+    #[test]
+    fn stopping_cancels_the_running_partial_pass() {
+        let (mut worker, _) = worker();
+        worker.start_streaming(StreamingSession::new(0, filter()));
+        worker.submit_partial(vec![0.0; BLOCK]);
+        std::thread::sleep(Duration::from_millis(50));
+        // Would block forever if the partial pass kept running
+        submit(&mut worker, 1);
+        let done = wait(&mut worker, Worker::try_done);
+        assert_eq!(done.result.unwrap().text(), "1");
+        assert!(
+            worker.try_partial().is_none(),
+            "cancelled result is dropped"
+        );
+        assert!(!worker.partial_pending());
+    }
+}
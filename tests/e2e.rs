@@ -1,5 +1,6 @@
 use std::path::Path;
 
+use dictr::config::WhisperConfig;
 use dictr::transcribe::{LocalWhisper, TranscribeBackend};
 
 /// Generate 2 seconds of 440Hz sine wave at 16kHz as f32 samples.
@@ -23,7 +24,8 @@ fn test_local_whisper_transcribes_audio() {
         return;
     }
 
-    let mut backend = LocalWhisper::new(&model_path).expect("failed to create LocalWhisper");
+    let mut backend = LocalWhisper::new(&model_path, WhisperConfig::default())
+        .expect("failed to create LocalWhisper");
     let audio = generate_test_audio();
 
     // A sine wave isn't speech, so we just verify the pipeline completes without error
@@ -42,7 +44,8 @@ fn test_local_whisper_with_language() {
         return;
     }
 
-    let mut backend = LocalWhisper::new(&model_path).expect("failed to create LocalWhisper");
+    let mut backend = LocalWhisper::new(&model_path, WhisperConfig::default())
+        .expect("failed to create LocalWhisper");
     let audio = generate_test_audio();
 
     let result = backend.transcribe(&audio, Some("en"), None);
@@ -64,7 +67,8 @@ fn test_local_whisper_with_initial_prompt() {
         return;
     }
 
-    let mut backend = LocalWhisper::new(&model_path).expect("failed to create LocalWhisper");
+    let mut backend = LocalWhisper::new(&model_path, WhisperConfig::default())
+        .expect("failed to create LocalWhisper");
     let audio = generate_test_audio();
 
     let result = backend.transcribe(&audio, Some("en"), Some("NixOS, Rust"));
@@ -86,7 +90,8 @@ fn test_local_whisper_empty_audio() {
         return;
     }
 
-    let mut backend = LocalWhisper::new(&model_path).expect("failed to create LocalWhisper");
+    let mut backend = LocalWhisper::new(&model_path, WhisperConfig::default())
+        .expect("failed to create LocalWhisper");
 
     // Empty audio should succeed with empty text, not panic
     let result = backend.transcribe(&[], None, None);